@@ -1,5 +1,5 @@
 from typing import Never
-from .events import TimeTrigger, wait_until_trigger
+from .events import TimeTrigger, TaskTrigger, wait_until_trigger, wait_until_any_trigger
 from .threads import Thread, Promise, submit, submit_global, Pool
 from .sync import Lock, Notification, Event
 from .mailbox import Mailbox, Message, Group
@@ -21,7 +21,9 @@ class jobs:
 
 __all__ = [
     "TimeTrigger",
+    "TaskTrigger",
     "wait_until_trigger",
+    "wait_until_any_trigger",
     "Thread",
     "Lock",
     "Notification",
@@ -10,7 +10,7 @@ def _new_thread_wrapper(info: dict[str, Any]) -> None:
 def new_thread(
     function: Callable[..., None], args: tuple[Any, ...], kwargs: dict[str, Any]
 ) -> None:
-    cc.new_thread(
+    cc.spawn_or_raise(
         _new_thread_wrapper,
         {
             "func": function,
@@ -36,17 +36,50 @@ class Trigger(abc.ABC):
 
     @property
     @abc.abstractmethod
-    def int_repr(self) -> list[int | str]:
+    def event(self) -> Any:
         raise NotImplementedError("Cannot use the default _Trigger as a trigger.")
 
+    @property
+    def timeout(self) -> float | None:
+        return None
+
+
+def wait_until_trigger(trigger: Trigger) -> None:
+    if trigger.event is None:
+        cc.sleep(trigger.timeout)
+    else:
+        cc.wait_any([trigger.event], trigger.timeout)
+
+
+def wait_until_any_trigger(triggers: list[Trigger]) -> int:
+    waiting: list[tuple[int, Any]] = [
+        (index, trigger.event) for index, trigger in enumerate(triggers) if trigger.event is not None
+    ]
+    timed: list[tuple[float, int]] = [
+        (trigger.timeout, index) for index, trigger in enumerate(triggers) if trigger.timeout is not None
+    ]
+    first_timeout: tuple[float, int] | None = min(timed, default=None)
+
+    if not waiting:
+        if first_timeout is None:
+            raise ValueError("wait_until_any_trigger needs at least one trigger.")
+
+        cc.sleep(first_timeout[0])
+        return first_timeout[1]
+
+    fired: int | None = cc.wait_any(
+        [event for _, event in waiting], None if first_timeout is None else first_timeout[0]
+    )
+
+    if fired is None:
+        return first_timeout[1]
 
-def wait_until_trigger(trigger: list[int | str]) -> None:
-    cc.wait_for_event(trigger)
+    return waiting[fired][0]
 
 
 class Lock:
     def __init__(self) -> None:
-        self._inner = cc.MutexLock()
+        self._inner = cc.MutexLock(strict=False)
 
     def acquire(self) -> None:
         self._inner.acquire()
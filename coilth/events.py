@@ -1,16 +1,36 @@
-from .cogs import Trigger, wait_until_trigger as _wait_until_trigger
+from typing import Any
+from .cogs import (
+    Trigger,
+    wait_until_trigger as _wait_until_trigger,
+    wait_until_any_trigger as _wait_until_any_trigger,
+)
 
 
 class TimeTrigger(Trigger):
-    id = 0x00
-
     def __init__(self, time: float) -> None:
         self._time = time
 
     @property
-    def int_repr(self) -> list[int | str]:
-        return [self.id, round(self._time * 10**9)]
+    def event(self) -> None:
+        return None
+
+    @property
+    def timeout(self) -> float:
+        return self._time
+
+
+class TaskTrigger(Trigger):
+    def __init__(self, handle: Any) -> None:
+        self._handle = handle
+
+    @property
+    def event(self) -> Any:
+        return self._handle
 
 
 def wait_until_trigger(trigger: Trigger) -> None:
-    _wait_until_trigger(trigger.int_repr)
+    _wait_until_trigger(trigger)
+
+
+def wait_until_any_trigger(*triggers: Trigger) -> int:
+    return _wait_until_any_trigger(list(triggers))
@@ -0,0 +1,109 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyCFunction, PyDict, PyModule, PyTuple};
+use tokio::task::AbortHandle;
+
+use crate::gil;
+use crate::runtime;
+
+// Runs on the event loop's thread, where the asyncio future may be touched. The future may have
+// been cancelled since the result was scheduled, in which case a result is handed to `undo`, if
+// there is one, and otherwise dropped.
+#[pyfunction]
+fn settle(future: &Bound<'_, PyAny>, value: Py<PyAny>, failed: bool, undo: Option<&Bound<'_, PyAny>>) -> PyResult<()> {
+    if future.call_method0("done")?.is_truthy()? {
+        if let Some(undo) = undo
+            && !failed
+        {
+            undo.call1((value,))?;
+        }
+
+        return Ok(());
+    }
+
+    match failed {
+        true => future.call_method1("set_exception", (value,))?,
+        false => future.call_method1("set_result", (value,))?,
+    };
+
+    Ok(())
+}
+
+/// The event loop running on this thread. Raises `RuntimeError` if there isn't one.
+pub fn running_loop(py: Python<'_>) -> PyResult<Bound<'_, PyAny>> {
+    PyModule::import(py, "asyncio")?.call_method0("get_running_loop")
+}
+
+/// Calls `on_cancel` (on the event loop's thread) if `future` ends up cancelled.
+pub fn on_cancelled<F>(future: &Bound<'_, PyAny>, on_cancel: F) -> PyResult<()>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let callback: Bound<'_, PyCFunction> = PyCFunction::new_closure(
+        future.py(),
+        None,
+        None,
+        move |args: &Bound<'_, PyTuple>, _kwargs: Option<&Bound<'_, PyDict>>| {
+            if args.get_item(0)?.call_method0("cancelled")?.is_truthy()? {
+                on_cancel();
+            }
+
+            Ok::<_, PyErr>(())
+        },
+    )?;
+
+    future.call_method1("add_done_callback", (callback,))?;
+
+    Ok(())
+}
+
+/// Returns an `asyncio.Future` on `event_loop` that resolves once `wait` finishes on coil's
+/// runtime, with what `outcome` then returns (or raises) under the GIL. The result is handed to
+/// the loop with `call_soon_threadsafe`, so nothing blocks it in the meantime. Cancelling the
+/// future stops `wait` at its next await point.
+pub fn awaitable<W, O>(event_loop: Bound<'_, PyAny>, wait: W) -> PyResult<PyObject>
+where
+    W: Future<Output = O> + Send + 'static,
+    O: FnOnce(Python<'_>) -> PyResult<PyObject> + Send + 'static,
+{
+    awaitable_with_undo(event_loop, wait, None)
+}
+
+/// Like `awaitable`, but a result that is ready only once the future has been cancelled is passed
+/// to `undo(value)` on the loop's thread instead of being dropped, e.g. to put back an item that
+/// was taken for it.
+pub fn awaitable_with_undo<W, O>(event_loop: Bound<'_, PyAny>, wait: W, undo: Option<Py<PyAny>>) -> PyResult<PyObject>
+where
+    W: Future<Output = O> + Send + 'static,
+    O: FnOnce(Python<'_>) -> PyResult<PyObject> + Send + 'static,
+{
+    let py: Python<'_> = event_loop.py();
+    let future: Bound<'_, PyAny> = event_loop.call_method0("create_future")?;
+    let settle: Py<PyAny> = wrap_pyfunction!(settle, py)?.into_any().unbind();
+
+    let event_loop: Py<PyAny> = event_loop.unbind();
+    let target: Py<PyAny> = future.clone().unbind();
+
+    let watcher: AbortHandle = runtime::handle()?
+        .spawn(async move {
+            let outcome: O = wait.await;
+
+            // Settling needs the GIL, so it gets a blocking thread rather than holding up a worker.
+            let _ = tokio::task::spawn_blocking(move || {
+                gil::attach(move |py| {
+                    let (value, failed): (PyObject, bool) = match outcome(py) {
+                        Ok(value) => (value, false),
+                        Err(error) => (error.into_value(py).into_any(), true),
+                    };
+
+                    // Fails only once the loop is closed, and then nobody is left waiting.
+                    let _ = event_loop.call_method1(py, "call_soon_threadsafe", (settle, target, value, failed, undo));
+                })
+            })
+            .await;
+        })
+        .abort_handle();
+
+    on_cancelled(&future, move || watcher.abort())?;
+
+    Ok(future.unbind())
+}
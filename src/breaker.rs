@@ -0,0 +1,146 @@
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyFunction, PyTuple};
+
+use crate::errors::CircuitOpen;
+use crate::runtime;
+
+const CLOSED: u8 = 0;
+const OPEN: u8 = 1;
+const HALF_OPEN: u8 = 2;
+
+struct BreakerState {
+    state: AtomicU8,
+    consecutive_failures: AtomicU64,
+    // Set while the single trial call let through in the half-open state is running.
+    probing: AtomicBool,
+    // Bumped every time the breaker opens, so a timer left over from an earlier opening (one
+    // followed by a `reset`) can't cut the current one short.
+    openings: AtomicU64,
+    failure_threshold: u64,
+    reset_timeout: Duration,
+}
+
+impl BreakerState {
+    fn open(self: &Arc<Self>) -> PyResult<()> {
+        let opening: u64 = self.openings.fetch_add(1, Ordering::SeqCst) + 1;
+        self.state.store(OPEN, Ordering::SeqCst);
+
+        let state: Arc<Self> = self.clone();
+
+        runtime::handle()?.spawn(async move {
+            tokio::time::sleep(state.reset_timeout).await;
+
+            if state.openings.load(Ordering::SeqCst) == opening {
+                let _ = state.state.compare_exchange(OPEN, HALF_OPEN, Ordering::SeqCst, Ordering::SeqCst);
+            }
+        });
+
+        Ok(())
+    }
+
+    fn record_success(&self, probe: bool) {
+        self.consecutive_failures.store(0, Ordering::SeqCst);
+
+        if probe {
+            self.state.store(CLOSED, Ordering::SeqCst);
+            self.probing.store(false, Ordering::SeqCst);
+        }
+    }
+
+    fn record_failure(self: &Arc<Self>, probe: bool) -> PyResult<()> {
+        let failures: u64 = self.consecutive_failures.fetch_add(1, Ordering::SeqCst) + 1;
+
+        if probe {
+            self.open()?;
+            self.probing.store(false, Ordering::SeqCst);
+        } else if failures >= self.failure_threshold
+            && self.state.compare_exchange(CLOSED, OPEN, Ordering::SeqCst, Ordering::SeqCst).is_ok()
+        {
+            self.open()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[pyclass(name = "CircuitBreaker")]
+#[derive(Clone)]
+pub struct PyCircuitBreaker {
+    inner: Arc<BreakerState>,
+}
+
+#[pymethods]
+impl PyCircuitBreaker {
+    /// Opens after `failure_threshold` consecutive failed calls, failing every call with
+    /// `CircuitOpen` for `reset_timeout_ns`. After that it is half-open: one trial call is let
+    /// through, which closes it again if it succeeds and reopens it if it fails.
+    #[new]
+    fn new(failure_threshold: u64, reset_timeout_ns: u64) -> PyResult<Self> {
+        if failure_threshold == 0 {
+            return Err(PyErr::new::<PyValueError, _>("'failure_threshold' must be positive."));
+        }
+
+        Ok(Self {
+            inner: Arc::new(BreakerState {
+                state: AtomicU8::new(CLOSED),
+                consecutive_failures: AtomicU64::new(0),
+                probing: AtomicBool::new(false),
+                openings: AtomicU64::new(0),
+                failure_threshold,
+                reset_timeout: Duration::from_nanos(reset_timeout_ns),
+            }),
+        })
+    }
+
+    /// Calls `py_func(*args)` and returns what it returns, or raises `CircuitOpen` without
+    /// calling it if the breaker is open (or half-open with its trial call still running).
+    /// Anything `py_func` raises counts as a failure and is re-raised.
+    #[pyo3(signature = (py_func, args = None))]
+    pub fn call(&self, py: Python<'_>, py_func: Py<PyFunction>, args: Option<Py<PyTuple>>) -> PyResult<PyObject> {
+        let probe: bool = match self.inner.state.load(Ordering::SeqCst) {
+            CLOSED => false,
+            HALF_OPEN if self.inner.probing.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_ok() => true,
+            _ => return Err(PyErr::new::<CircuitOpen, _>("The circuit breaker is open.")),
+        };
+
+        let args: Bound<'_, PyTuple> = match args {
+            Some(args) => args.into_bound(py),
+            None => PyTuple::empty(py),
+        };
+        let result: PyResult<PyObject> = py_func.call1(py, args);
+
+        match &result {
+            Ok(_) => self.inner.record_success(probe),
+            Err(_) => self.inner.record_failure(probe)?,
+        }
+
+        result
+    }
+
+    /// Closes the breaker and clears its failure count.
+    pub fn reset(&self) {
+        self.inner.consecutive_failures.store(0, Ordering::SeqCst);
+        self.inner.state.store(CLOSED, Ordering::SeqCst);
+        self.inner.probing.store(false, Ordering::SeqCst);
+    }
+
+    /// `"closed"`, `"open"` or `"half_open"`.
+    #[getter]
+    pub fn state(&self) -> &'static str {
+        match self.inner.state.load(Ordering::SeqCst) {
+            CLOSED => "closed",
+            OPEN => "open",
+            _ => "half_open",
+        }
+    }
+
+    #[getter]
+    pub fn consecutive_failures(&self) -> u64 {
+        self.inner.consecutive_failures.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,104 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyFunction, PyTuple};
+use tokio::sync::{AcquireError, OwnedSemaphorePermit, Semaphore};
+
+use crate::errors::BulkheadFull;
+use crate::runtime;
+
+struct BulkheadState {
+    slots: Arc<Semaphore>,
+    // Callers currently blocked waiting for a slot.
+    waiting: AtomicUsize,
+    max_concurrent: usize,
+    max_queue: usize,
+}
+
+struct WaiterGuard<'a>(&'a AtomicUsize);
+
+impl Drop for WaiterGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl BulkheadState {
+    fn acquire(&self, py: Python<'_>) -> PyResult<OwnedSemaphorePermit> {
+        if let Ok(permit) = self.slots.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        // Counted before checking, so two callers racing for the last place in the queue can't
+        // both get it.
+        let _waiter: WaiterGuard<'_> = WaiterGuard(&self.waiting);
+
+        if self.waiting.fetch_add(1, Ordering::SeqCst) >= self.max_queue {
+            return Err(PyErr::new::<BulkheadFull, _>(format!(
+                "All {} slots are in use and {} callers are already waiting.", self.max_concurrent, self.max_queue
+            )));
+        }
+
+        let slots: Arc<Semaphore> = self.slots.clone();
+
+        py.allow_threads(move || runtime::block_on(slots.acquire_owned()))?
+            .map_err(|e: AcquireError| PyErr::new::<PyRuntimeError, _>(format!("Failed to acquire a bulkhead slot: {}", e)))
+    }
+}
+
+#[pyclass(name = "Bulkhead")]
+#[derive(Clone)]
+pub struct PyBulkhead {
+    inner: Arc<BulkheadState>,
+}
+
+#[pymethods]
+impl PyBulkhead {
+    /// Lets at most `max_concurrent` calls through `execute` at once, with up to `max_queue` more
+    /// waiting for a slot. Calls beyond that fail straight away with `BulkheadFull`, so a slow
+    /// dependency behind the bulkhead can only ever tie up that many threads.
+    #[new]
+    fn new(max_concurrent: usize, max_queue: usize) -> PyResult<Self> {
+        if max_concurrent == 0 {
+            return Err(PyErr::new::<PyValueError, _>("'max_concurrent' must be positive."));
+        }
+
+        Ok(Self {
+            inner: Arc::new(BulkheadState {
+                slots: Arc::new(Semaphore::new(max_concurrent)),
+                waiting: AtomicUsize::new(0),
+                max_concurrent,
+                max_queue,
+            }),
+        })
+    }
+
+    /// Calls `py_func(*args)` once a slot is free, waiting with the GIL released if the queue has
+    /// room, and returns what it returns. Raises `BulkheadFull` if it would have to wait and the
+    /// queue is full.
+    #[pyo3(signature = (py_func, args = None))]
+    pub fn execute(&self, py: Python<'_>, py_func: Py<PyFunction>, args: Option<Py<PyTuple>>) -> PyResult<PyObject> {
+        let _permit: OwnedSemaphorePermit = self.inner.acquire(py)?;
+
+        let args: Bound<'_, PyTuple> = match args {
+            Some(args) => args.into_bound(py),
+            None => PyTuple::empty(py),
+        };
+
+        py_func.call1(py, args)
+    }
+
+    /// How many calls are running.
+    #[getter]
+    pub fn active(&self) -> usize {
+        self.inner.max_concurrent - self.inner.slots.available_permits()
+    }
+
+    /// How many calls are waiting for a slot.
+    #[getter]
+    pub fn waiting(&self) -> usize {
+        self.inner.waiting.load(Ordering::SeqCst)
+    }
+}
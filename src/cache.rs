@@ -0,0 +1,293 @@
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::Duration;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{PyAny, PyCFunction, PyDict, PyFrozenSet, PyFunction, PyModule, PyTuple};
+use tokio::task::AbortHandle;
+
+use crate::flight::PySingleFlight;
+use crate::gil;
+use crate::runtime;
+
+struct TtlCacheInner {
+    // Key -> `(expires_at_ns, value)`, in insertion order, so the first entry is always the oldest.
+    entries: Py<PyDict>,
+    max_entries: usize,
+    ttl_ns: u64,
+    // The task dropping expired entries, while there are entries for it to watch.
+    sweeper: Mutex<Option<AbortHandle>>,
+}
+
+impl TtlCacheInner {
+    fn sweeper(&self) -> MutexGuard<'_, Option<AbortHandle>> {
+        self.sweeper.lock().expect("Cache sweeper mutex was poisoned.")
+    }
+
+    fn sweep(&self, py: Python<'_>) -> PyResult<()> {
+        let entries: &Bound<'_, PyDict> = self.entries.bind(py);
+        let now: u64 = runtime::now_ns();
+
+        let expired: Vec<Bound<'_, PyAny>> = entries
+            .iter()
+            .filter_map(|(key, entry)| match entry.get_item(0).and_then(|expires| expires.extract::<u64>()) {
+                Ok(expires_at) if expires_at <= now => Some(Ok(key)),
+                Ok(_) => None,
+                Err(error) => Some(Err(error)),
+            })
+            .collect::<PyResult<_>>()?;
+
+        for key in expired {
+            entries.del_item(key)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Holds only a weak reference, so the sweeper stops once the cache itself is gone.
+async fn sweep_periodically(inner: Weak<TtlCacheInner>, every: Duration) {
+    loop {
+        tokio::time::sleep(every).await;
+
+        let Some(inner) = inner.upgrade() else { break };
+
+        // Decided under the GIL, which `set` holds too, so an entry set after this finds the
+        // cache empty sees the sweeper gone and starts a new one.
+        let stopped: PyResult<bool> = gil::attach(|py| {
+            if let Err(error) = inner.sweep(py) {
+                error.print(py);
+            }
+
+            let empty: bool = inner.entries.bind(py).is_empty();
+
+            if empty {
+                *inner.sweeper() = None;
+            }
+
+            empty
+        });
+
+        if stopped.unwrap_or(true) {
+            break
+        }
+    }
+}
+
+impl Drop for TtlCacheInner {
+    fn drop(&mut self) {
+        if let Some(sweeper) = self.sweeper().take() {
+            sweeper.abort();
+        }
+    }
+}
+
+fn check_arguments(max_entries: usize, ttl_ns: u64) -> PyResult<()> {
+    if max_entries == 0 || ttl_ns == 0 {
+        return Err(PyErr::new::<PyValueError, _>("'max_entries' and 'ttl_ns' must both be positive."));
+    }
+
+    Ok(())
+}
+
+#[pyclass(name = "TtlCache")]
+#[derive(Clone)]
+pub struct PyTtlCache {
+    inner: Arc<TtlCacheInner>,
+}
+
+impl PyTtlCache {
+    // Also restarts a sweeper that went down with an idle runtime.
+    fn start_sweeper(&self) -> PyResult<()> {
+        let mut sweeper: MutexGuard<'_, Option<AbortHandle>> = self.inner.sweeper();
+
+        if sweeper.as_ref().is_none_or(AbortHandle::is_finished) {
+            let every: Duration = Duration::from_nanos(self.inner.ttl_ns).clamp(Duration::from_millis(1), Duration::from_secs(1));
+
+            *sweeper = Some(runtime::spawn_background(sweep_periodically(Arc::downgrade(&self.inner), every))?);
+        }
+
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl PyTtlCache {
+    /// A cache of at most `max_entries` entries, each of which expires `ttl_ns` after it was set.
+    /// Expired entries are dropped in the background by a task on coil's runtime, started by the
+    /// first `set` and stopped whenever the cache is empty, which doesn't keep the runtime from
+    /// counting as idle.
+    #[new]
+    pub fn new(py: Python<'_>, max_entries: usize, ttl_ns: u64) -> PyResult<Self> {
+        check_arguments(max_entries, ttl_ns)?;
+
+        let inner: Arc<TtlCacheInner> = Arc::new(TtlCacheInner {
+            entries: PyDict::new(py).unbind(),
+            max_entries,
+            ttl_ns,
+            sweeper: Mutex::new(None),
+        });
+
+        Ok(Self { inner })
+    }
+
+    /// Returns the value set for `key`, or `default` if there is none or it has expired.
+    #[pyo3(signature = (key, default = None))]
+    pub fn get(&self, py: Python<'_>, key: Py<PyAny>, default: Option<Py<PyAny>>) -> PyResult<Option<Py<PyAny>>> {
+        let entries: &Bound<'_, PyDict> = self.inner.entries.bind(py);
+
+        if let Some(entry) = entries.get_item(&key)? {
+            let (expires_at, value): (u64, Py<PyAny>) = entry.extract()?;
+
+            if expires_at > runtime::now_ns() {
+                return Ok(Some(value));
+            }
+
+            entries.del_item(&key)?;
+        }
+
+        Ok(default)
+    }
+
+    /// Sets `key` to `value`, restarting its TTL. If that takes the cache over `max_entries`, the
+    /// entry that was set longest ago is evicted.
+    pub fn set(&self, py: Python<'_>, key: Py<PyAny>, value: Py<PyAny>) -> PyResult<()> {
+        self.start_sweeper()?;
+
+        let entries: &Bound<'_, PyDict> = self.inner.entries.bind(py);
+        let expires_at: u64 = runtime::now_ns().saturating_add(self.inner.ttl_ns);
+
+        // Removing first moves the key to the end of the insertion order.
+        if entries.contains(&key)? {
+            entries.del_item(&key)?;
+        }
+
+        entries.set_item(&key, PyTuple::new(py, [expires_at.into_pyobject(py)?.into_any(), value.into_bound(py)])?)?;
+
+        while entries.len() > self.inner.max_entries {
+            let oldest: Bound<'_, PyAny> = entries.keys().get_item(0)?;
+            entries.del_item(oldest)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn delete(&self, py: Python<'_>, key: Py<PyAny>) -> PyResult<()> {
+        let entries: &Bound<'_, PyDict> = self.inner.entries.bind(py);
+
+        if entries.contains(&key)? {
+            entries.del_item(&key)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn clear(&self, py: Python<'_>) {
+        self.inner.entries.bind(py).clear();
+    }
+
+    fn __contains__(&self, py: Python<'_>, key: Py<PyAny>) -> PyResult<bool> {
+        Ok(self.get(py, key, None)?.is_some())
+    }
+
+    /// The number of entries held, which may include expired ones the sweeper hasn't reached yet.
+    fn __len__(&self, py: Python<'_>) -> usize {
+        self.inner.entries.bind(py).len()
+    }
+}
+
+/// A function wrapped by `cached`.
+#[pyclass(name = "CachedFunction")]
+pub struct PyCachedFunction {
+    py_func: Py<PyFunction>,
+    // Built on the first call, so decorating a function costs nothing until it is used.
+    cache: GILOnceCell<PyTtlCache>,
+    max_entries: usize,
+    ttl_ns: u64,
+    flight: PySingleFlight,
+    // Stored as the default for cache lookups, so a miss can be told apart from a cached `None`.
+    missing: Py<PyAny>,
+    // Put between the positional and keyword arguments in a key, like `functools`' `_kwd_mark`,
+    // so `f(a, b=1)` can't share a key with a positional call that happens to look the same.
+    kwargs_mark: Py<PyAny>,
+}
+
+impl PyCachedFunction {
+    fn cache(&self, py: Python<'_>) -> PyResult<&PyTtlCache> {
+        self.cache.get_or_try_init(py, || PyTtlCache::new(py, self.max_entries, self.ttl_ns))
+    }
+}
+
+#[pymethods]
+impl PyCachedFunction {
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(&self, py: Python<'_>, args: Py<PyTuple>, kwargs: Option<Py<PyDict>>) -> PyResult<PyObject> {
+        let key: Py<PyAny> = match &kwargs {
+            Some(kwargs) if !kwargs.bind(py).is_empty() => {
+                let kwargs: Bound<'_, PyFrozenSet> = PyFrozenSet::new(py, kwargs.bind(py).items().iter())?;
+                let key: Vec<Bound<'_, PyAny>> = args
+                    .bind(py)
+                    .iter()
+                    .chain([self.kwargs_mark.bind(py).clone(), kwargs.into_any()])
+                    .collect();
+
+                PyTuple::new(py, key)?.into_any().unbind()
+            }
+            _ => args.clone_ref(py).into_any(),
+        };
+
+        let cache: &PyTtlCache = self.cache(py)?;
+        let cached: Option<Py<PyAny>> = cache.get(py, key.clone_ref(py), Some(self.missing.clone_ref(py)))?;
+
+        if let Some(value) = cached.filter(|value: &Py<PyAny>| !value.is(&self.missing)) {
+            return Ok(value);
+        }
+
+        self.flight.run(py, key.clone_ref(py), |py_call| {
+            let value: PyObject = self.py_func.bind(py_call).call(args.bind(py_call), kwargs.as_ref().map(|kwargs| kwargs.bind(py_call)))?.unbind();
+
+            cache.set(py_call, key, value.clone_ref(py_call))?;
+
+            Ok(value)
+        })
+    }
+
+    /// Forgets every cached result.
+    fn cache_clear(&self, py: Python<'_>) {
+        if let Some(cache) = self.cache.get(py) {
+            cache.clear(py);
+        }
+    }
+
+    #[getter]
+    fn __wrapped__(&self, py: Python<'_>) -> Py<PyFunction> {
+        self.py_func.clone_ref(py)
+    }
+}
+
+/// A decorator that memoizes a function by its arguments, which must all be hashable. Results
+/// are kept for `ttl_ns` (at most `max_entries` of them, dropping the oldest), and concurrent
+/// calls with the same arguments wait for the first one instead of computing the result again.
+/// Exceptions are passed on to every waiting caller but never cached.
+#[pyfunction]
+#[pyo3(signature = (ttl_ns, *, max_entries = 1024))]
+pub fn cached(py: Python<'_>, ttl_ns: u64, max_entries: usize) -> PyResult<Bound<'_, PyCFunction>> {
+    // Checked up front, so a bad argument fails at the decorator rather than at the function.
+    check_arguments(max_entries, ttl_ns)?;
+
+    PyCFunction::new_closure(py, None, None, move |args: &Bound<'_, PyTuple>, _kwargs: Option<&Bound<'_, PyDict>>| {
+        let py: Python<'_> = args.py();
+        let py_func: Py<PyFunction> = args.get_item(0)?.extract()?;
+
+        Ok::<_, PyErr>(PyCachedFunction {
+            py_func,
+            cache: GILOnceCell::new(),
+            max_entries,
+            ttl_ns,
+            flight: PySingleFlight::new(py),
+            missing: PyModule::import(py, "builtins")?.getattr("object")?.call0()?.unbind(),
+            kwargs_mark: PyModule::import(py, "builtins")?.getattr("object")?.call0()?.unbind(),
+        })
+    })
+}
@@ -0,0 +1,251 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use pyo3::exceptions::{PyStopIteration, PyTimeoutError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyCFunction, PyDict, PyTuple};
+use tokio::sync::mpsc::{self, error::TryRecvError};
+use tokio::sync::Notify;
+
+use crate::aio;
+use crate::errors::QueueClosed;
+use crate::runtime;
+
+#[derive(Clone)]
+enum ChannelSender {
+    Bounded(mpsc::Sender<Py<PyAny>>),
+    Unbounded(mpsc::UnboundedSender<Py<PyAny>>),
+}
+
+enum ChannelReceiver {
+    Bounded(mpsc::Receiver<Py<PyAny>>),
+    Unbounded(mpsc::UnboundedReceiver<Py<PyAny>>),
+}
+
+impl ChannelReceiver {
+    async fn recv(&mut self) -> Option<Py<PyAny>> {
+        match self {
+            Self::Bounded(receiver) => receiver.recv().await,
+            Self::Unbounded(receiver) => receiver.recv().await,
+        }
+    }
+
+    fn try_recv(&mut self) -> Result<Py<PyAny>, TryRecvError> {
+        match self {
+            Self::Bounded(receiver) => receiver.try_recv(),
+            Self::Unbounded(receiver) => receiver.try_recv(),
+        }
+    }
+}
+
+struct ChannelInner {
+    // Taken by `close`. Once the last clone of it (held only for the length of a `send`) is gone,
+    // receivers get what is left and then `None`.
+    sender: Mutex<Option<ChannelSender>>,
+    // `mpsc` has a single receiver; parking consumers on this lock lets any number share it, in
+    // the order they arrived.
+    receiver: tokio::sync::Mutex<ChannelReceiver>,
+    // Items a cancelled `recv_async` had already taken, handed out again before anything still in
+    // the channel.
+    returned: Mutex<VecDeque<Py<PyAny>>>,
+    item_returned: Notify,
+}
+
+impl ChannelInner {
+    fn sender(&self) -> MutexGuard<'_, Option<ChannelSender>> {
+        self.sender.lock().expect("Channel sender mutex was poisoned.")
+    }
+
+    fn take_returned(&self) -> Option<Py<PyAny>> {
+        self.returned.lock().expect("Channel returned items mutex was poisoned.").pop_front()
+    }
+
+    fn give_back(&self, item: Py<PyAny>) {
+        self.returned.lock().expect("Channel returned items mutex was poisoned.").push_back(item);
+        self.item_returned.notify_waiters();
+    }
+
+    async fn recv(&self) -> Option<Py<PyAny>> {
+        let mut receiver: tokio::sync::MutexGuard<'_, ChannelReceiver> = self.receiver.lock().await;
+
+        loop {
+            let returned = self.item_returned.notified();
+            tokio::pin!(returned);
+            returned.as_mut().enable();
+
+            if let Some(item) = self.take_returned() {
+                return Some(item);
+            }
+
+            // `recv` is cancel-safe, so giving up on it when an item comes back loses nothing.
+            tokio::select! {
+                received = receiver.recv() => return received.or_else(|| self.take_returned()),
+                _ = &mut returned => continue,
+            }
+        }
+    }
+}
+
+fn closed_error() -> PyErr {
+    PyErr::new::<QueueClosed, _>("The channel is closed.")
+}
+
+fn timeout_duration(seconds: f64) -> PyResult<Duration> {
+    Duration::try_from_secs_f64(seconds.max(0.0))
+        .map_err(|_| PyErr::new::<PyValueError, _>(format!("'timeout' must be a finite number, got {seconds}.")))
+}
+
+/// A first-in, first-out channel over `tokio::sync::mpsc`, for handing items between tasks. With
+/// `maxsize` it is bounded and `send` waits for room; with the default of 0 it is unbounded.
+/// Any number of threads may send and receive.
+#[pyclass(name = "Channel")]
+#[derive(Clone)]
+pub struct PyChannel {
+    inner: Arc<ChannelInner>,
+}
+
+#[pymethods]
+impl PyChannel {
+    #[new]
+    #[pyo3(signature = (maxsize = 0))]
+    fn new(maxsize: usize) -> Self {
+        let (sender, receiver): (ChannelSender, ChannelReceiver) = match maxsize {
+            0 => {
+                let (sender, receiver) = mpsc::unbounded_channel::<Py<PyAny>>();
+                (ChannelSender::Unbounded(sender), ChannelReceiver::Unbounded(receiver))
+            }
+            maxsize => {
+                let (sender, receiver) = mpsc::channel::<Py<PyAny>>(maxsize);
+                (ChannelSender::Bounded(sender), ChannelReceiver::Bounded(receiver))
+            }
+        };
+
+        Self {
+            inner: Arc::new(ChannelInner {
+                sender: Mutex::new(Some(sender)),
+                receiver: tokio::sync::Mutex::new(receiver),
+                returned: Mutex::new(VecDeque::new()),
+                item_returned: Notify::new(),
+            }),
+        }
+    }
+
+    /// Sends `item`, waiting with the GIL released while a bounded channel is full. Raises
+    /// `QueueClosed` if the channel has been closed.
+    pub fn send(&self, py: Python<'_>, item: Py<PyAny>) -> PyResult<()> {
+        let sender: ChannelSender = self.inner.sender().clone().ok_or_else(closed_error)?;
+
+        match sender {
+            ChannelSender::Unbounded(sender) => sender.send(item).map_err(|_| closed_error()),
+            ChannelSender::Bounded(sender) => {
+                py.allow_threads(move || runtime::block_on(async move { sender.send(item).await }))?.map_err(|_| closed_error())
+            }
+        }
+    }
+
+    /// Waits, with the GIL released, for the next item. With `timeout` (in seconds), raises
+    /// `TimeoutError` if none arrives in time. Once the channel is closed and empty, raises
+    /// `QueueClosed`.
+    #[pyo3(signature = (timeout = None))]
+    pub fn recv(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<Py<PyAny>> {
+        let timeout: Option<Duration> = timeout.map(timeout_duration).transpose()?;
+        let inner: Arc<ChannelInner> = self.inner.clone();
+
+        let received: Option<Option<Py<PyAny>>> = py.allow_threads(move || {
+            runtime::block_on(async move {
+                let receive = inner.recv();
+
+                match timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, receive).await.ok(),
+                    None => Some(receive.await),
+                }
+            })
+        })?;
+
+        match received {
+            Some(Some(item)) => Ok(item),
+            Some(None) => Err(closed_error()),
+            None => Err(PyErr::new::<PyTimeoutError, _>(format!("Nothing was received within {:?}.", timeout.unwrap_or_default()))),
+        }
+    }
+
+    /// Like `recv`, but returns an `asyncio.Future` on the running event loop, to await from a
+    /// coroutine instead of blocking. Give a timeout as `timeout` rather than with
+    /// `asyncio.wait_for`, which can throw away an item that arrives just as it times out. An item
+    /// that arrives as the future is cancelled goes to the next receiver rather than being lost.
+    #[pyo3(signature = (timeout = None))]
+    pub fn recv_async(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<PyObject> {
+        let timeout: Option<Duration> = timeout.map(timeout_duration).transpose()?;
+        let event_loop: Bound<'_, PyAny> = aio::running_loop(py)?;
+        let inner: Arc<ChannelInner> = self.inner.clone();
+        let returned: Arc<ChannelInner> = self.inner.clone();
+
+        let undo: Bound<'_, PyCFunction> = PyCFunction::new_closure(
+            py,
+            None,
+            None,
+            move |args: &Bound<'_, PyTuple>, _kwargs: Option<&Bound<'_, PyDict>>| {
+                returned.give_back(args.get_item(0)?.unbind());
+
+                Ok::<_, PyErr>(())
+            },
+        )?;
+
+        aio::awaitable_with_undo(event_loop, async move {
+            let received: Option<Option<Py<PyAny>>> = match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, inner.recv()).await.ok(),
+                None => Some(inner.recv().await),
+            };
+
+            move |_: Python<'_>| match received {
+                Some(Some(item)) => Ok(item),
+                Some(None) => Err(closed_error()),
+                None => Err(PyErr::new::<PyTimeoutError, _>(format!(
+                    "Nothing was received within {:?}.",
+                    timeout.unwrap_or_default()
+                ))),
+            }
+        }, Some(undo.into_any().unbind()))
+    }
+
+    /// Returns the next item if one is waiting, and `None` otherwise, including while another
+    /// thread is in `recv`. Like `recv`, raises once the channel is closed and empty.
+    pub fn try_recv(&self) -> PyResult<Option<Py<PyAny>>> {
+        if let Some(item) = self.inner.take_returned() {
+            return Ok(Some(item));
+        }
+
+        let Ok(mut receiver) = self.inner.receiver.try_lock() else { return Ok(None) };
+
+        match receiver.try_recv() {
+            Ok(item) => Ok(Some(item)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(closed_error()),
+        }
+    }
+
+    /// Stops any more items from being sent. Items already in the channel can still be received.
+    pub fn close(&self) {
+        self.inner.sender().take();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.sender().is_none()
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Iterating ends once the channel is closed and empty.
+    fn __next__(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        self.recv(py, None).map_err(|error: PyErr| {
+            if error.is_instance_of::<QueueClosed>(py) {
+                PyErr::new::<PyStopIteration, _>(())
+            } else {
+                error
+            }
+        })
+    }
+}
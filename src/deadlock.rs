@@ -0,0 +1,102 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use pyo3::exceptions::PyRuntimeWarning;
+use pyo3::prelude::*;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+// `a -> b` means some thread has acquired `b` while holding `a`.
+static LOCK_ORDER: Lazy<Mutex<HashMap<u64, HashSet<u64>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+thread_local! {
+    static HELD_LOCKS: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Whether `to` has been acquired, directly or through a chain of other locks, while `from` was held.
+fn is_ordered_before(graph: &HashMap<u64, HashSet<u64>>, from: u64, to: u64) -> bool {
+    let mut seen: HashSet<u64> = HashSet::new();
+    let mut pending: Vec<u64> = vec![from];
+
+    while let Some(lock) = pending.pop() {
+        if lock == to {
+            return true
+        }
+
+        if seen.insert(lock) {
+            pending.extend(graph.get(&lock).into_iter().flatten());
+        }
+    }
+
+    false
+}
+
+/// Called before blocking on `lock`. Warns if taking it while holding this thread's other locks
+/// contradicts an order seen before, i.e. if two threads doing so at once could deadlock.
+pub fn check_acquire(py: Python<'_>, lock: u64) -> PyResult<()> {
+    let held: Vec<u64> = HELD_LOCKS.with(|held: &RefCell<Vec<u64>>| held.borrow().clone());
+    let inverted: Option<u64> = {
+        let graph = LOCK_ORDER.lock().expect("Lock order mutex was poisoned.");
+
+        held.iter().copied().find(|&other| other != lock && is_ordered_before(&graph, lock, other))
+    };
+
+    if let Some(other) = inverted {
+        let message: CString = CString::new(format!(
+            "Potential deadlock: acquiring MutexLock #{} while holding MutexLock #{}, but they have previously been acquired in the opposite order.",
+            lock, other,
+        ))?;
+
+        PyErr::warn(py, &py.get_type::<PyRuntimeWarning>(), &message, 1)?;
+    }
+
+    Ok(())
+}
+
+/// Called once `lock` has been acquired on this thread.
+pub fn record_acquire(lock: u64) {
+    HELD_LOCKS.with(|held: &RefCell<Vec<u64>>| {
+        let mut held = held.borrow_mut();
+        let mut graph = LOCK_ORDER.lock().expect("Lock order mutex was poisoned.");
+
+        for &other in held.iter().filter(|&&other| other != lock) {
+            graph.entry(other).or_default().insert(lock);
+        }
+
+        held.push(lock);
+    });
+}
+
+/// Called when `lock` is released. A lock released from a thread other than the one that acquired
+/// it is not found here, and stays on the acquiring thread's stack.
+pub fn record_release(lock: u64) {
+    HELD_LOCKS.with(|held: &RefCell<Vec<u64>>| {
+        let mut held = held.borrow_mut();
+
+        if let Some(position) = held.iter().rposition(|&other| other == lock) {
+            held.remove(position);
+        }
+    });
+}
+
+/// Starts (or, with `enabled=False`, stops) tracking the order in which each thread acquires
+/// `MutexLock`s, emitting a `RuntimeWarning` whenever a lock is about to be taken in an order that
+/// contradicts one seen earlier. This slows every acquire down, so it is meant for debugging.
+/// Turn warnings into errors with the `warnings` module to fail on the first inversion.
+#[pyfunction]
+#[pyo3(signature = (enabled = true))]
+pub fn enable_deadlock_detection(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+
+    if !enabled {
+        LOCK_ORDER.lock().expect("Lock order mutex was poisoned.").clear();
+    }
+}
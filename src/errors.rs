@@ -0,0 +1,57 @@
+use std::sync::{Arc, Mutex};
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyFunction;
+
+use crate::gil;
+
+create_exception!(coil_core, TaskTimeout, PyException, "A task did not finish within the time it was given.");
+create_exception!(coil_core, TaskPanicked, PyException, "A task ended in a Rust panic rather than a Python exception.");
+create_exception!(coil_core, TaskCancelled, PyException, "A task was cancelled before it finished.");
+create_exception!(coil_core, CircuitOpen, PyException, "A circuit breaker is open and refused the call.");
+create_exception!(coil_core, BulkheadFull, PyException, "A bulkhead had no free slot and no room left in its queue.");
+create_exception!(coil_core, ExecutorSaturated, PyException, "Every task slot allowed by `set_max_concurrent_tasks` is in use.");
+create_exception!(coil_core, LockError, PyException, "A lock was released by a thread that doesn't hold it.");
+create_exception!(coil_core, QueueClosed, PyException, "A queue or subscription has been closed.");
+
+static EXCEPTION_HANDLER: Mutex<Option<Py<PyFunction>>> = Mutex::new(None);
+
+/// Reports an exception that has nowhere to propagate to, e.g. one raised by a hook or by a task
+/// nothing holds a handle to. `context` is the object it came from. Goes to the handler set with
+/// `set_exception_handler`, or else to `sys.unraisablehook`.
+pub fn report_error(py: Python<'_>, error: PyErr, context: &Bound<'_, PyAny>) {
+    let handler: Option<Py<PyFunction>> = EXCEPTION_HANDLER
+        .lock()
+        .expect("Exception handler mutex was poisoned.")
+        .as_ref()
+        .map(|handler| handler.clone_ref(py));
+
+    let Some(handler) = handler else {
+        return error.write_unraisable(py, Some(context));
+    };
+
+    if let Err(handler_error) = handler.call1(py, (error.into_value(py), context)) {
+        handler_error.write_unraisable(py, Some(handler.bind(py).as_any()));
+    }
+}
+
+/// `report_error` for code running on the runtime. The handler is arbitrary Python, so it is
+/// called on the blocking pool rather than tying up a worker thread for as long as it takes.
+pub async fn report_error_async<T: 'static>(error: PyErr, context: Arc<Py<T>>) {
+    let _ = tokio::task::spawn_blocking(move || {
+        gil::attach(move |py| report_error(py, error, context.bind(py).as_any()))
+    })
+    .await;
+}
+
+/// Sets `py_func(exception, context)` to be called with every exception coil has nowhere else to
+/// send: ones raised by tasks whose handles have all been dropped (`context` is then the task's
+/// `TaskHandle`), by hooks, timers, pool handlers and the like. `None` goes back to the default,
+/// reporting them through `sys.unraisablehook`, which prints them to stderr. An exception raised
+/// by the handler itself is reported that way too.
+#[pyfunction]
+pub fn set_exception_handler(py_func: Option<Py<PyFunction>>) {
+    *EXCEPTION_HANDLER.lock().expect("Exception handler mutex was poisoned.") = py_func;
+}
@@ -0,0 +1,251 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use pyo3::exceptions::{PyDeprecationWarning, PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBool;
+use tokio::sync::Notify;
+use tokio::task::{JoinError, JoinSet};
+use tokio::time::Duration;
+
+use crate::aio;
+use crate::runtime;
+use crate::task::{self, PyTaskHandle, TaskState};
+
+const SLEEP_EVENT: i128 = 0x00;
+const TASK_EVENT: i128 = 0x01;
+
+pub enum Event {
+    Sleep(Duration),
+    /// `None` when the task has already finished and nothing holds its handle any more.
+    Task(Option<Arc<TaskState>>),
+    Flag(Arc<EventFlag>),
+}
+
+impl Event {
+    pub fn parse(arguments: &[i128]) -> PyResult<Self> {
+        match arguments {
+            [SLEEP_EVENT, nanos] => {
+                let nanos: u64 = u64::try_from(*nanos).map_err(|_| {
+                    PyErr::new::<PyValueError, _>(format!("Invalid sleep duration, '{nanos}'."))
+                })?;
+
+                Ok(Self::Sleep(Duration::from_nanos(nanos)))
+            }
+            [TASK_EVENT, id] => {
+                let id: u64 = u64::try_from(*id)
+                    .ok()
+                    .filter(|id: &u64| task::was_issued(*id))
+                    .ok_or_else(|| PyErr::new::<PyValueError, _>(format!("Unknown task id, '{id}'.")))?;
+
+                Ok(Self::Task(task::lookup_task(id)))
+            }
+            [SLEEP_EVENT | TASK_EVENT, ..] => Err(PyErr::new::<PyValueError, _>(
+                format!("Wrong number of arguments for event id '{}'.", arguments[0])
+            )),
+            [other, ..] => Err(PyErr::new::<PyValueError, _>(format!("Unknown event id, '{other}'."))),
+            [] => Err(PyErr::new::<PyValueError, _>("Missing event id.")),
+        }
+    }
+
+    pub async fn wait(&self) {
+        match self {
+            Self::Sleep(duration) => tokio::time::sleep(*duration).await,
+            Self::Task(Some(state)) => state.wait().await,
+            Self::Task(None) => (),
+            Self::Flag(flag) => flag.wait().await,
+        }
+    }
+
+    /// Accepts an `Event`, a `TaskHandle`, or the deprecated list form taken by `wait_for_event`.
+    fn extract(py: Python<'_>, event: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if let Ok(event) = event.downcast::<PyEvent>() {
+            return Ok(Self::Flag(event.borrow().flag.clone()));
+        }
+
+        if let Ok(handle) = event.downcast::<PyTaskHandle>() {
+            return Ok(Self::Task(Some(handle.borrow().state().clone())));
+        }
+
+        let arguments: Vec<i128> = event.extract().map_err(|_| {
+            PyErr::new::<PyTypeError, _>(format!("Expected an Event or a TaskHandle, got {}.", event.get_type()))
+        })?;
+
+        warn_deprecated_arguments(py)?;
+
+        Self::parse(&arguments)
+    }
+}
+
+fn warn_deprecated_arguments(py: Python<'_>) -> PyResult<()> {
+    PyErr::warn(
+        py,
+        &py.get_type::<PyDeprecationWarning>(),
+        c"Integer event lists are deprecated; use `sleep`, `Event` or a `TaskHandle` instead.",
+        2,
+    )
+}
+
+pub struct EventFlag {
+    set: AtomicBool,
+    changed: Notify,
+}
+
+impl EventFlag {
+    async fn wait(&self) {
+        loop {
+            let notified = self.changed.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if self.set.load(Ordering::SeqCst) {
+                break
+            }
+
+            notified.await;
+        }
+    }
+}
+
+/// A flag threads can wait on, like `threading.Event`, but waiting with the GIL released on
+/// coil's runtime. It can also be passed to `wait_any`.
+#[pyclass(name = "Event")]
+#[derive(Clone)]
+pub struct PyEvent {
+    flag: Arc<EventFlag>,
+}
+
+#[pymethods]
+impl PyEvent {
+    #[new]
+    fn new() -> Self {
+        Self {
+            flag: Arc::new(EventFlag { set: AtomicBool::new(false), changed: Notify::new() }),
+        }
+    }
+
+    /// Sets the flag, waking everyone waiting on it.
+    pub fn set(&self) {
+        self.flag.set.store(true, Ordering::SeqCst);
+        self.flag.changed.notify_waiters();
+    }
+
+    /// Clears the flag, so waiting blocks again until the next `set`.
+    pub fn clear(&self) {
+        self.flag.set.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.flag.set.load(Ordering::SeqCst)
+    }
+
+    /// Waits until the flag is set, for at most `timeout` seconds if given. Returns whether it
+    /// was set.
+    #[pyo3(signature = (timeout = None))]
+    pub fn wait(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<bool> {
+        let timeout: Option<Duration> = timeout.map(timeout_duration).transpose()?;
+        let flag: Arc<EventFlag> = self.flag.clone();
+
+        py.allow_threads(move || {
+            runtime::block_on(async move {
+                match timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, flag.wait()).await.is_ok(),
+                    None => {
+                        flag.wait().await;
+                        true
+                    }
+                }
+            })
+        })
+    }
+
+    /// Like `wait`, but returns an `asyncio.Future` on the running event loop that resolves to
+    /// `True` once the flag is set. For a timeout, wrap it in `asyncio.wait_for`.
+    pub fn wait_async(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let flag: Arc<EventFlag> = self.flag.clone();
+
+        aio::awaitable(aio::running_loop(py)?, async move {
+            flag.wait().await;
+
+            |py: Python<'_>| Ok(PyBool::new(py, true).to_owned().into_any().unbind())
+        })
+    }
+}
+
+fn timeout_duration(seconds: f64) -> PyResult<Duration> {
+    Duration::try_from_secs_f64(seconds.max(0.0))
+        .map_err(|_| PyErr::new::<PyValueError, _>(format!("'timeout' must be a finite number, got {seconds}.")))
+}
+
+/// Blocks (with the GIL released) until the event fires. Task events return the task's result,
+/// or re-raise its exception, once it finishes.
+///
+/// Deprecated: use `sleep`, `Event.wait` or `TaskHandle.join` instead.
+#[pyfunction]
+pub fn wait_for_event(py: Python<'_>, arguments: Vec<i128>) -> PyResult<PyObject> {
+    warn_deprecated_arguments(py)?;
+
+    let event: Event = Event::parse(&arguments)?;
+
+    let event: Event = py.allow_threads(move || runtime::block_on(async move {
+        event.wait().await;
+        event
+    }))?;
+
+    match event {
+        Event::Task(Some(state)) => state.output(py),
+        _ => Ok(py.None()),
+    }
+}
+
+/// Sleeps for `seconds`, which may be fractional, with the GIL released so other threads keep
+/// running. The same as waiting on a sleep event, without having to spell one out.
+#[pyfunction]
+pub fn sleep(py: Python<'_>, seconds: f64) -> PyResult<()> {
+    let duration: Duration = Duration::try_from_secs_f64(seconds).map_err(|_| {
+        PyErr::new::<PyValueError, _>(format!("'seconds' must be a finite, non-negative number, got {seconds}."))
+    })?;
+
+    py.allow_threads(move || runtime::block_on(async move { tokio::time::sleep(duration).await }))
+}
+
+/// Blocks until the first of `events` fires and returns its index, or `None` if `timeout` (in
+/// seconds) runs out first. Each event is an `Event` (fires once set) or a `TaskHandle` (fires
+/// once the task finishes); the integer lists `wait_for_event` takes still work but are
+/// deprecated. Events that lose the race are left untouched, so a task that outlived a timeout
+/// can still be joined or waited on again.
+#[pyfunction]
+#[pyo3(signature = (events, timeout = None))]
+pub fn wait_any(py: Python<'_>, events: Vec<Bound<'_, PyAny>>, timeout: Option<f64>) -> PyResult<Option<usize>> {
+    if events.is_empty() {
+        return Err(PyErr::new::<PyValueError, _>("wait_any needs at least one event."));
+    }
+
+    let timeout: Option<Duration> = timeout.map(timeout_duration).transpose()?;
+    let events: Vec<Event> = events.iter().map(|event| Event::extract(py, event)).collect::<PyResult<_>>()?;
+
+    py.allow_threads(move || runtime::block_on(async move {
+        let mut set: JoinSet<usize> = JoinSet::new();
+
+        for (index, event) in events.into_iter().enumerate() {
+            set.spawn(async move {
+                event.wait().await;
+                index
+            });
+        }
+
+        let first = async {
+            set.join_next()
+                .await
+                .expect("wait_any always waits on at least one event.")
+                .map_err(|e: JoinError| {
+                    PyErr::new::<PyRuntimeError, _>(format!("Failed to wait for event (task join error): {}", e))
+                })
+        };
+
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, first).await.ok().transpose(),
+            None => first.await.map(Some),
+        }
+    }))?
+}
@@ -0,0 +1,89 @@
+use std::sync::{Mutex, MutexGuard};
+
+use once_cell::sync::Lazy;
+use tokio::sync::Notify;
+
+// Coil-run callables normally run side by side; an exclusive one waits for all of them to finish
+// and keeps any more from starting until it is done. Unlike `tokio::sync::RwLock` this doesn't
+// queue new shared callers behind a waiting exclusive one, since a callable joining another one
+// that got stuck behind an exclusive call would then deadlock.
+#[derive(Default)]
+struct ExclusionState {
+    shared: usize,
+    exclusive: bool,
+}
+
+static EXCLUSION: Lazy<Mutex<ExclusionState>> = Lazy::new(|| Mutex::new(ExclusionState::default()));
+static EXCLUSION_CHANGED: Notify = Notify::const_new();
+
+fn exclusion() -> MutexGuard<'static, ExclusionState> {
+    EXCLUSION.lock().expect("Exclusion mutex was poisoned.")
+}
+
+pub struct SharedGuard;
+
+impl Drop for SharedGuard {
+    fn drop(&mut self) {
+        let idle: bool = {
+            let mut state: MutexGuard<'_, ExclusionState> = exclusion();
+            // Saturating, since a guard taken before a fork can be dropped after `reset`.
+            state.shared = state.shared.saturating_sub(1);
+            state.shared == 0
+        };
+
+        if idle {
+            EXCLUSION_CHANGED.notify_waiters();
+        }
+    }
+}
+
+pub struct ExclusiveGuard;
+
+impl Drop for ExclusiveGuard {
+    fn drop(&mut self) {
+        exclusion().exclusive = false;
+        EXCLUSION_CHANGED.notify_waiters();
+    }
+}
+
+async fn wait_until<T>(mut try_enter: impl FnMut(&mut ExclusionState) -> Option<T>) -> T {
+    loop {
+        let notified = EXCLUSION_CHANGED.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+
+        if let Some(guard) = try_enter(&mut exclusion()) {
+            return guard
+        }
+
+        notified.await;
+    }
+}
+
+/// Forgets every guard handed out so far. Only for a forked child, where the callables holding
+/// them don't exist.
+pub fn reset() {
+    *exclusion() = ExclusionState::default();
+}
+
+/// Waits out any exclusive callable, then lets this one run alongside the other shared ones.
+pub async fn shared() -> SharedGuard {
+    wait_until(|state: &mut ExclusionState| {
+        (!state.exclusive).then(|| {
+            state.shared += 1;
+            SharedGuard
+        })
+    })
+    .await
+}
+
+/// Waits until no other callable is running, then keeps every other one from starting.
+pub async fn exclusive() -> ExclusiveGuard {
+    wait_until(|state: &mut ExclusionState| {
+        (!state.exclusive && state.shared == 0).then(|| {
+            state.exclusive = true;
+            ExclusiveGuard
+        })
+    })
+    .await
+}
@@ -0,0 +1,115 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict, PyFunction, PyTuple};
+use tokio::sync::Notify;
+
+use crate::errors;
+use crate::runtime;
+
+struct FlightState {
+    outcome: Mutex<Option<PyResult<PyObject>>>,
+    done: Notify,
+}
+
+impl FlightState {
+    fn outcome(&self) -> MutexGuard<'_, Option<PyResult<PyObject>>> {
+        self.outcome.lock().expect("Flight outcome mutex was poisoned.")
+    }
+
+    async fn wait(&self) {
+        loop {
+            let notified = self.done.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if self.outcome().is_some() {
+                break
+            }
+
+            notified.await;
+        }
+    }
+}
+
+// Only ever stored in `PySingleFlight::flights`, so the in-progress calls can be looked up by any
+// hashable Python key.
+#[pyclass]
+struct Flight {
+    state: Arc<FlightState>,
+}
+
+#[pyclass(name = "SingleFlight")]
+pub struct PySingleFlight {
+    flights: Py<PyDict>,
+}
+
+impl PySingleFlight {
+    /// Runs `call` as the call for `key`, or waits for the one already in progress. See `do`.
+    pub fn run<F>(&self, py: Python<'_>, key: Py<PyAny>, call: F) -> PyResult<PyObject>
+    where
+        F: FnOnce(Python<'_>) -> PyResult<PyObject>,
+    {
+        let flights: &Bound<'_, PyDict> = self.flights.bind(py);
+
+        if let Some(existing) = flights.get_item(&key)? {
+            let state: Arc<FlightState> = existing.downcast::<Flight>()?.borrow().state.clone();
+            let waited: Arc<FlightState> = state.clone();
+
+            py.allow_threads(move || runtime::block_on(async move { waited.wait().await }))?;
+
+            return match state.outcome().as_ref().expect("Flight finished without an outcome.") {
+                Ok(value) => Ok(value.clone_ref(py)),
+                Err(error) => Err(error.clone_ref(py)),
+            };
+        }
+
+        let state: Arc<FlightState> = Arc::new(FlightState { outcome: Mutex::new(None), done: Notify::new() });
+
+        flights.set_item(&key, Flight { state: state.clone() })?;
+
+        let result: PyResult<PyObject> = call(py);
+
+        // Settled before the key is removed, since removing it runs the key's `__eq__`, which can
+        // raise, and the followers mustn't be left waiting on a flight that never lands.
+        *state.outcome() = Some(match &result {
+            Ok(value) => Ok(value.clone_ref(py)),
+            Err(error) => Err(error.clone_ref(py)),
+        });
+        state.done.notify_waiters();
+
+        if let Err(error) = flights.del_item(&key) {
+            errors::report_error(py, error, key.bind(py));
+        }
+
+        result
+    }
+}
+
+#[pymethods]
+impl PySingleFlight {
+    #[new]
+    pub fn new(py: Python<'_>) -> Self {
+        Self { flights: PyDict::new(py).unbind() }
+    }
+
+    /// Calls `py_func(*args)` and returns its result, unless a call for `key` is already in
+    /// progress, in which case this waits for that call and returns (or raises) what it did.
+    /// Nothing is kept once the call finishes; the next `do` for `key` calls `py_func` again.
+    #[pyo3(signature = (key, py_func, args = None))]
+    pub fn r#do(&self, py: Python<'_>, key: Py<PyAny>, py_func: Py<PyFunction>, args: Option<Py<PyTuple>>) -> PyResult<PyObject> {
+        self.run(py, key, move |py_call| {
+            let args: Bound<'_, PyTuple> = match args {
+                Some(args) => args.into_bound(py_call),
+                None => PyTuple::empty(py_call),
+            };
+
+            py_func.call1(py_call, args)
+        })
+    }
+
+    /// How many keys currently have a call in progress.
+    pub fn in_flight(&self, py: Python<'_>) -> usize {
+        self.flights.bind(py).len()
+    }
+}
@@ -0,0 +1,38 @@
+use std::path::{Path, PathBuf};
+
+use pyo3::exceptions::PyOSError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::runtime;
+
+// Building the OSError from (errno, strerror, filename) lets Python pick the matching subclass,
+// so a missing file raises FileNotFoundError with `.filename` set, just like `open` would.
+fn os_error(error: std::io::Error, path: &Path) -> PyErr {
+    match error.raw_os_error() {
+        Some(errno) => PyErr::new::<PyOSError, _>((errno, error.to_string(), path.to_path_buf())),
+        None => PyErr::from(error),
+    }
+}
+
+/// Reads a whole file through the runtime's IO threads, with the GIL released for the duration.
+#[pyfunction]
+pub fn read_file(py: Python<'_>, path: PathBuf) -> PyResult<Py<PyBytes>> {
+    let target: PathBuf = path.clone();
+
+    let data: Vec<u8> = py
+        .allow_threads(move || runtime::block_on(async move { tokio::fs::read(target).await }))?
+        .map_err(|e: std::io::Error| os_error(e, &path))?;
+
+    Ok(PyBytes::new(py, &data).unbind())
+}
+
+/// Writes `data` to `path`, creating or truncating it, with the GIL released for the duration.
+#[pyfunction]
+pub fn write_file(py: Python<'_>, path: PathBuf, data: &[u8]) -> PyResult<()> {
+    let target: PathBuf = path.clone();
+    let data: Vec<u8> = data.to_vec();
+
+    py.allow_threads(move || runtime::block_on(async move { tokio::fs::write(target, data).await }))?
+        .map_err(|e: std::io::Error| os_error(e, &path))
+}
@@ -0,0 +1,197 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use pyo3::exceptions::{PyTimeoutError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyModule};
+
+use crate::errors;
+use crate::gil;
+use crate::hooks;
+use crate::runtime;
+use crate::task::{PyTaskHandle, TaskOutput, TaskState};
+
+#[derive(Default)]
+struct DoneCallbacks {
+    pending: Vec<Py<PyAny>>,
+    // Whether a task is waiting to run `pending` once the future is done.
+    watching: bool,
+    // Whether that task has already taken `pending`; anything added after runs straight away.
+    fired: bool,
+}
+
+struct FutureInner {
+    handle: PyTaskHandle,
+    callbacks: Mutex<DoneCallbacks>,
+}
+
+impl FutureInner {
+    fn callbacks(&self) -> MutexGuard<'_, DoneCallbacks> {
+        self.callbacks.lock().expect("Future callbacks mutex was poisoned.")
+    }
+
+    fn state(&self) -> &Arc<TaskState> {
+        self.handle.state()
+    }
+}
+
+fn cancelled_error(py: Python<'_>) -> PyErr {
+    match PyModule::import(py, "concurrent.futures").and_then(|module| module.getattr("CancelledError")) {
+        Ok(cancelled) => PyErr::from_value(cancelled),
+        Err(error) => error,
+    }
+}
+
+fn run_callbacks(py: Python<'_>, inner: &Arc<FutureInner>, callbacks: Vec<Py<PyAny>>) {
+    let future: Bound<'_, PyFuture> = match Bound::new(py, PyFuture { inner: inner.clone() }) {
+        Ok(future) => future,
+        Err(error) => return error.print(py),
+    };
+
+    for callback in callbacks {
+        if let Err(error) = callback.call1(py, (&future,)) {
+            errors::report_error(py, error, callback.bind(py));
+        }
+    }
+}
+
+async fn watch(inner: Arc<FutureInner>) {
+    inner.state().wait().await;
+
+    let callbacks: Vec<Py<PyAny>> = {
+        let mut callbacks: MutexGuard<'_, DoneCallbacks> = inner.callbacks();
+        callbacks.fired = true;
+        std::mem::take(&mut callbacks.pending)
+    };
+
+    // Callbacks are arbitrary Python code, so they get a blocking thread like any callable.
+    let _ = tokio::task::spawn_blocking(move || {
+        gil::attach(|py| {
+            hooks::prepare_thread(py);
+            run_callbacks(py, &inner, callbacks);
+        })
+    })
+    .await;
+}
+
+/// The result of a task started with `submit`, with the same methods as
+/// `concurrent.futures.Future`, so coil can stand in for a `ThreadPoolExecutor`. Timeouts are in
+/// seconds, as in the standard library.
+#[pyclass(name = "Future")]
+#[derive(Clone)]
+pub struct PyFuture {
+    inner: Arc<FutureInner>,
+}
+
+impl PyFuture {
+    pub fn new(handle: PyTaskHandle) -> Self {
+        Self {
+            inner: Arc::new(FutureInner { handle, callbacks: Mutex::new(DoneCallbacks::default()) }),
+        }
+    }
+
+    // Waits for the task, raising `TimeoutError` if it is still running after `timeout` seconds
+    // and `CancelledError` if it was cancelled.
+    fn outcome(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<TaskOutput> {
+        let timeout: Option<Duration> = timeout
+            .map(|seconds: f64| {
+                Duration::try_from_secs_f64(seconds.max(0.0)).map_err(|_| {
+                    PyErr::new::<PyValueError, _>(format!("'timeout' must be a finite number, got {seconds}."))
+                })
+            })
+            .transpose()?;
+
+        let state: Arc<TaskState> = self.inner.state().clone();
+        let finished: bool = py.allow_threads(move || {
+            runtime::block_on(async move {
+                match timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, state.wait()).await.is_ok(),
+                    None => {
+                        state.wait().await;
+                        true
+                    }
+                }
+            })
+        })?;
+
+        if self.inner.handle.is_cancelled() {
+            return Err(cancelled_error(py));
+        }
+
+        if !finished {
+            return Err(PyErr::new::<PyTimeoutError, _>(format!("The task did not finish within {:?}.", timeout.unwrap_or_default())));
+        }
+
+        Ok(self.inner.state().output(py))
+    }
+}
+
+#[pymethods]
+impl PyFuture {
+    /// Waits up to `timeout` seconds (forever if `None`) and returns what the callable returned,
+    /// or re-raises what it raised.
+    #[pyo3(signature = (timeout = None))]
+    pub fn result(&self, py: Python<'_>, timeout: Option<f64>) -> TaskOutput {
+        self.outcome(py, timeout)?
+    }
+
+    /// Waits like `result`, but returns the exception the callable raised, or `None` if it
+    /// returned normally.
+    #[pyo3(signature = (timeout = None))]
+    pub fn exception(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<Option<PyObject>> {
+        Ok(self.outcome(py, timeout)?.err().map(|error: PyErr| error.into_value(py).into_any()))
+    }
+
+    pub fn done(&self) -> bool {
+        self.inner.state().is_finished()
+    }
+
+    /// Cancels the task, returning `False` if it had already finished. Like `TaskHandle.cancel`,
+    /// a callable that has already started runs to completion in the background.
+    pub fn cancel(&self) -> bool {
+        !self.done() && self.inner.handle.cancel()
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.inner.handle.is_cancelled()
+    }
+
+    /// Calls `fn(future)` once the task is done, on one of coil's threads, or straight away on
+    /// this one if it already is. Exceptions raised by `fn` are reported and otherwise ignored.
+    pub fn add_done_callback(&self, py: Python<'_>, r#fn: Py<PyAny>) -> PyResult<()> {
+        let mut callbacks: MutexGuard<'_, DoneCallbacks> = self.inner.callbacks();
+
+        // Nothing is pending unless something is watching, so a finished task with no watcher can
+        // run the callback here.
+        if callbacks.fired || (!callbacks.watching && self.done()) {
+            drop(callbacks);
+            run_callbacks(py, &self.inner, vec![r#fn]);
+            return Ok(());
+        }
+
+        callbacks.pending.push(r#fn);
+
+        if !callbacks.watching {
+            runtime::handle()?.spawn(watch(self.inner.clone()));
+            callbacks.watching = true;
+        }
+
+        Ok(())
+    }
+
+    /// The `TaskHandle` of the task behind this future.
+    #[getter]
+    pub fn handle(&self) -> PyTaskHandle {
+        self.inner.handle.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        let status: &str = match (self.cancelled(), self.done()) {
+            (true, _) => "cancelled",
+            (false, true) => "finished",
+            (false, false) => "pending",
+        };
+
+        format!("Future(id={}, {status})", self.inner.handle.id())
+    }
+}
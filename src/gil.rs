@@ -0,0 +1,45 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+
+// Set from an `atexit` hook. Once the interpreter starts finalizing, a thread that isn't already
+// attached can block forever trying to take the GIL, so coil's own threads check this first.
+static FINALIZING: AtomicBool = AtomicBool::new(false);
+
+#[pyfunction]
+pub fn mark_finalizing() {
+    FINALIZING.store(true, Ordering::SeqCst);
+}
+
+/// Why this thread can't take the GIL right now, if it can't.
+fn unavailable() -> Option<&'static str> {
+    // Safety: `Py_IsInitialized` may be called at any time, with or without the GIL.
+    if unsafe { pyo3::ffi::Py_IsInitialized() } == 0 {
+        Some("The Python interpreter is not initialized.")
+    } else if FINALIZING.load(Ordering::SeqCst) {
+        Some("The Python interpreter is shutting down.")
+    } else {
+        None
+    }
+}
+
+/// Raises the `RuntimeError` `attach` would, without attaching. Lets work that will need the GIL
+/// on one of coil's threads fail where it is started rather than later, where nobody sees it.
+pub fn check_available() -> PyResult<()> {
+    match unavailable() {
+        Some(reason) => Err(PyErr::new::<PyRuntimeError, _>(reason)),
+        None => Ok(()),
+    }
+}
+
+/// `Python::with_gil` for coil's own threads: returns a `RuntimeError` instead of panicking or
+/// hanging when the interpreter isn't there to attach to.
+pub fn attach<F, R>(f: F) -> PyResult<R>
+where
+    F: for<'py> FnOnce(Python<'py>) -> R,
+{
+    check_available()?;
+
+    Ok(Python::with_gil(f))
+}
@@ -0,0 +1,311 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use pyo3::exceptions::{PyRuntimeError, PyTimeoutError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyFunction};
+use tokio::sync::Notify;
+
+use crate::priority::Priority;
+use crate::runtime;
+use crate::task::PyTaskHandle;
+use crate::{spawn_thread, SpawnOptions};
+
+static NEXT_GROUP_ID: AtomicU64 = AtomicU64::new(1);
+
+// Every group with tasks or a handle still around, for `fetch_metrics`.
+static GROUPS: Lazy<Mutex<Vec<Weak<GroupInner>>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+fn groups() -> MutexGuard<'static, Vec<Weak<GroupInner>>> {
+    GROUPS.lock().expect("Task group registry mutex was poisoned.")
+}
+
+/// How many of a group's tasks are in each state.
+#[derive(Clone, Copy, Default)]
+pub struct GroupCounts {
+    pub alive: u64,
+    pub finished: u64,
+    pub failed: u64,
+}
+
+#[derive(Default)]
+struct GroupState {
+    // Handles of the tasks that haven't finished, for `cancel_all`.
+    running: Vec<PyTaskHandle>,
+    counts: GroupCounts,
+    // The first task to fail, in the order they finished. Cancelled tasks don't count.
+    first_failure: Option<PyTaskHandle>,
+}
+
+struct GroupInner {
+    name: String,
+    fail_fast: bool,
+    cancelled: AtomicBool,
+    state: Mutex<GroupState>,
+    // Notified whenever the last running task finishes.
+    idle: Notify,
+}
+
+impl GroupInner {
+    fn state(&self) -> MutexGuard<'_, GroupState> {
+        self.state.lock().expect("Task group state mutex was poisoned.")
+    }
+
+    fn cancel_all(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+
+        // Cancelled outside the lock, since a cancelled task's watcher takes it to report back.
+        let running: Vec<PyTaskHandle> = self.state().running.clone();
+
+        for handle in running {
+            handle.cancel();
+        }
+    }
+
+    async fn wait_idle(&self) {
+        loop {
+            let notified = self.idle.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if self.state().counts.alive == 0 {
+                break
+            }
+
+            notified.await;
+        }
+    }
+}
+
+// Waits for one of the group's tasks and books its outcome.
+async fn watch(inner: Arc<GroupInner>, handle: PyTaskHandle) {
+    handle.state().wait().await;
+
+    let failed: bool = handle.state().failed() && !handle.is_cancelled();
+
+    let idle: bool = {
+        let mut state: MutexGuard<'_, GroupState> = inner.state();
+
+        state.running.retain(|running: &PyTaskHandle| running.id() != handle.id());
+        state.counts.alive -= 1;
+        state.counts.finished += 1;
+
+        if failed {
+            state.counts.failed += 1;
+            state.first_failure.get_or_insert(handle);
+        }
+
+        state.counts.alive == 0
+    };
+
+    if failed && inner.fail_fast {
+        inner.cancel_all();
+    }
+
+    if idle {
+        inner.idle.notify_waiters();
+    }
+}
+
+/// Task counts for every live group, summed over groups that share a name.
+pub fn counts() -> Vec<(String, GroupCounts)> {
+    let mut groups: MutexGuard<'_, Vec<Weak<GroupInner>>> = groups();
+    let mut counts: Vec<(String, GroupCounts)> = Vec::new();
+
+    groups.retain(|group: &Weak<GroupInner>| group.strong_count() > 0);
+
+    for group in groups.iter().filter_map(Weak::upgrade) {
+        let group_counts: GroupCounts = group.state().counts;
+
+        match counts.iter_mut().find(|(name, _)| *name == group.name) {
+            Some((_, total)) => {
+                total.alive += group_counts.alive;
+                total.finished += group_counts.finished;
+                total.failed += group_counts.failed;
+            }
+            None => counts.push((group.name.clone(), group_counts)),
+        }
+    }
+
+    counts
+}
+
+/// Forgets every group. Only for a forked child, where their tasks never finish.
+pub fn reset() {
+    groups().clear();
+}
+
+/// A set of tasks that are waited on and cancelled together. `with TaskGroup() as group:` joins
+/// every task spawned into it when the block ends, cancelling them first if the block raised.
+///
+/// With `fail_fast`, the first task to raise cancels the rest. Either way, `join_all` re-raises
+/// the first failure once every task is done. `name` labels the group in `fetch_metrics`.
+#[pyclass(name = "TaskGroup")]
+#[derive(Clone)]
+pub struct PyTaskGroup {
+    inner: Arc<GroupInner>,
+}
+
+#[pymethods]
+impl PyTaskGroup {
+    #[new]
+    #[pyo3(signature = (name = None, *, fail_fast = false))]
+    fn new(name: Option<String>, fail_fast: bool) -> Self {
+        let id: u64 = NEXT_GROUP_ID.fetch_add(1, Ordering::Relaxed);
+        let inner: Arc<GroupInner> = Arc::new(GroupInner {
+            name: name.unwrap_or_else(|| format!("group-{id}")),
+            fail_fast,
+            cancelled: AtomicBool::new(false),
+            state: Mutex::new(GroupState::default()),
+            idle: Notify::new(),
+        });
+
+        groups().push(Arc::downgrade(&inner));
+
+        Self { inner }
+    }
+
+    /// Spawns `py_func(arg)` like `spawn_or_raise`, as part of the group. Raises `RuntimeError`
+    /// once the group has been cancelled.
+    #[pyo3(signature = (py_func, arg, *, priority = "normal", exclusive = false, pass_handle = false))]
+    pub fn spawn(
+        &self,
+        py: Python<'_>,
+        py_func: Py<PyFunction>,
+        arg: Py<PyAny>,
+        priority: &str,
+        exclusive: bool,
+        pass_handle: bool,
+    ) -> PyResult<PyTaskHandle> {
+        let priority: Priority = Priority::parse(priority)?;
+
+        if self.inner.cancelled.load(Ordering::SeqCst) {
+            return Err(PyErr::new::<PyRuntimeError, _>(format!("Task group '{}' has been cancelled.", self.inner.name)));
+        }
+
+        // Taken first, so nothing below can fail once the task is running and counted.
+        let runtime: tokio::runtime::Handle = runtime::handle()?;
+        let handle: PyTaskHandle = spawn_thread(py, py_func, arg, SpawnOptions { priority, exclusive, pass_handle, parent: None })?;
+
+        {
+            let mut state: MutexGuard<'_, GroupState> = self.inner.state();
+            state.running.push(handle.clone());
+            state.counts.alive += 1;
+        }
+
+        runtime.spawn(watch(self.inner.clone(), handle.clone()));
+
+        // A cancel that raced with this spawn may have missed the new task.
+        if self.inner.cancelled.load(Ordering::SeqCst) {
+            handle.cancel();
+        }
+
+        Ok(handle)
+    }
+
+    /// Waits, with the GIL released, until every task in the group has finished, including ones
+    /// spawned while waiting. Raises `TimeoutError` if that takes longer than `timeout` seconds,
+    /// and otherwise re-raises the first exception a task raised, if any did.
+    #[pyo3(signature = (timeout = None))]
+    pub fn join_all(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<()> {
+        let timeout: Option<Duration> = timeout
+            .map(|seconds: f64| {
+                Duration::try_from_secs_f64(seconds.max(0.0)).map_err(|_| {
+                    PyErr::new::<PyValueError, _>(format!("'timeout' must be a finite number, got {seconds}."))
+                })
+            })
+            .transpose()?;
+
+        let inner: Arc<GroupInner> = self.inner.clone();
+        let finished: bool = py.allow_threads(move || {
+            runtime::block_on(async move {
+                match timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, inner.wait_idle()).await.is_ok(),
+                    None => {
+                        inner.wait_idle().await;
+                        true
+                    }
+                }
+            })
+        })?;
+
+        if !finished {
+            return Err(PyErr::new::<PyTimeoutError, _>(format!(
+                "Task group '{}' did not finish within {:?}.",
+                self.inner.name,
+                timeout.unwrap_or_default()
+            )));
+        }
+
+        let first_failure: Option<PyTaskHandle> = self.inner.state().first_failure.clone();
+
+        match first_failure {
+            Some(failed) => failed.state().output(py).map(drop),
+            None => Ok(()),
+        }
+    }
+
+    /// Cancels every task in the group and keeps any more from being spawned into it. Callables
+    /// that have already started run to completion in the background, as with
+    /// `TaskHandle.cancel`.
+    pub fn cancel_all(&self) {
+        self.inner.cancel_all();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    #[getter]
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// How many of the group's tasks haven't finished yet.
+    #[getter]
+    pub fn alive(&self) -> u64 {
+        self.inner.state().counts.alive
+    }
+
+    /// How many of the group's tasks have raised, not counting cancelled ones.
+    #[getter]
+    pub fn failed(&self) -> u64 {
+        self.inner.state().counts.failed
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &self,
+        py: Python<'_>,
+        exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        if exc_type.is_some() {
+            self.cancel_all();
+        }
+
+        let joined: PyResult<()> = self.join_all(py, None);
+
+        // The block's own exception takes precedence over whatever the tasks raised.
+        if exc_type.is_none() {
+            joined?;
+        }
+
+        Ok(false)
+    }
+
+    fn __repr__(&self) -> String {
+        let counts: GroupCounts = self.inner.state().counts;
+
+        format!(
+            "TaskGroup(name={:?}, alive={}, finished={}, failed={})",
+            self.inner.name, counts.alive, counts.finished, counts.failed
+        )
+    }
+}
@@ -0,0 +1,85 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyFunction};
+
+use crate::errors;
+
+static THREAD_INIT: Lazy<Mutex<Option<Py<PyFunction>>>> = Lazy::new(|| Mutex::new(None));
+static TASK_START: Lazy<Mutex<Option<Py<PyFunction>>>> = Lazy::new(|| Mutex::new(None));
+static TASK_FINISH: Lazy<Mutex<Option<Py<PyFunction>>>> = Lazy::new(|| Mutex::new(None));
+
+fn current(py: Python<'_>, hook: &Mutex<Option<Py<PyFunction>>>) -> Option<Py<PyFunction>> {
+    hook.lock().expect("Hook mutex was poisoned.").as_ref().map(|hook| hook.clone_ref(py))
+}
+
+// Bumped by every `set_thread_init`, so threads that ran an older initializer run the new one.
+static THREAD_INIT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static INITIALIZED_GENERATION: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Registers `py_func` to be called, with no arguments, on each blocking-pool thread before the
+/// first coil callable it runs, e.g. to set BLAS or torch thread counts, which are per thread.
+/// Replaces any earlier initializer and runs again on threads that already ran the old one;
+/// `None` removes it. An exception from it is reported and the callable runs anyway.
+#[pyfunction]
+pub fn set_thread_init(py_func: Option<Py<PyFunction>>) {
+    *THREAD_INIT.lock().expect("Hook mutex was poisoned.") = py_func;
+    THREAD_INIT_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Runs the thread initializer on this thread if it hasn't run the current one yet. Called at the
+/// top of every blocking closure that runs Python code.
+pub fn prepare_thread(py: Python<'_>) {
+    let generation: u64 = THREAD_INIT_GENERATION.load(Ordering::SeqCst);
+
+    if INITIALIZED_GENERATION.with(Cell::get) == generation {
+        return
+    }
+
+    INITIALIZED_GENERATION.with(|initialized: &Cell<u64>| initialized.set(generation));
+
+    if let Some(init) = current(py, &THREAD_INIT)
+        && let Err(error) = init.call0(py)
+    {
+        errors::report_error(py, error, init.bind(py).as_any());
+    }
+}
+
+/// Registers `py_func(task_id)` to be called right before each coil callable runs, on the thread
+/// that runs it. `None` removes it. Exceptions from it are reported and don't affect the task.
+#[pyfunction]
+pub fn on_task_start(py_func: Option<Py<PyFunction>>) {
+    *TASK_START.lock().expect("Hook mutex was poisoned.") = py_func;
+}
+
+/// Registers `py_func(task_id, exception)` to be called right after each coil callable returns,
+/// on the thread that ran it, with `exception` set to what it raised or `None` if it returned.
+/// `None` removes it. Exceptions from it are reported and don't affect the task.
+#[pyfunction]
+pub fn on_task_finish(py_func: Option<Py<PyFunction>>) {
+    *TASK_FINISH.lock().expect("Hook mutex was poisoned.") = py_func;
+}
+
+pub fn task_started(py: Python<'_>, task_id: Option<u64>) {
+    if let Some(hook) = current(py, &TASK_START)
+        && let Err(error) = hook.call1(py, (task_id,))
+    {
+        errors::report_error(py, error, hook.bind(py).as_any());
+    }
+}
+
+pub fn task_finished(py: Python<'_>, task_id: Option<u64>, result: &PyResult<PyObject>) {
+    if let Some(hook) = current(py, &TASK_FINISH) {
+        let exception: Option<Bound<'_, PyAny>> = result.as_ref().err().map(|error| error.value(py).clone().into_any());
+
+        if let Err(error) = hook.call1(py, (task_id, exception)) {
+            errors::report_error(py, error, hook.bind(py).as_any());
+        }
+    }
+}
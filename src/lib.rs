@@ -1,157 +1,844 @@
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::pin::Pin;
+use std::sync::{Arc, Barrier, Mutex, MutexGuard};
+use std::thread::ThreadId;
+use std::task::Poll;
+use std::time::Duration;
 
 use pyo3::exceptions::{PyRuntimeError, PyValueError};
 use pyo3::{prelude::*};
 use pyo3::types::{PyAny, PyBool, PyDict, PyFunction, PyModule};
-use tokio::runtime::{Runtime};
-use once_cell::sync::Lazy;
-use tokio::sync::Notify;
-use tokio::task::JoinHandle;
-use tokio::time::Duration;
+use tokio::sync::{OwnedMutexGuard, OwnedSemaphorePermit};
+use tokio::task::{JoinError, JoinSet};
 
+mod aio;
+mod breaker;
+mod bulkhead;
+mod cache;
+mod channel;
+mod deadlock;
+mod errors;
+mod events;
+mod exclusive;
+mod flight;
+mod fs;
+mod future;
+mod gil;
+mod group;
+mod hooks;
+mod local;
+mod limit;
+mod log;
+mod metrics;
+mod panic;
+mod pool;
+mod priority;
+mod pubsub;
+mod queue;
+mod runtime;
+mod schedule;
+mod sync;
+mod task;
+mod timer;
+mod watchdog;
 
-static TOKIO_RUNTIME: Lazy<Runtime> = Lazy::new(|| {
-    tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .expect("Failed to create tokio runtime.")
-});
+use errors::LockError;
+use metrics::MetricsSnapshot;
+use priority::{Priority, PriorityPermit};
+use sync::PySemaphore;
+use task::PyTaskHandle;
 
 mod internal {
+    use std::sync::Arc;
+
     use pyo3::prelude::*;
+    use pyo3::sync::GILOnceCell;
     use pyo3::types::{PyFunction, PyList, PyModule, PyString, PyAny};
 
+    use crate::{exclusive, gil, hooks, task};
+    use crate::task::PyTaskHandle;
+
+    static PYTHON_PATH_READY: GILOnceCell<()> = GILOnceCell::new();
+
+    /// Makes the working directory importable from spawned callables. Only does anything the
+    /// first time it succeeds, and leaves `sys.path` alone if `"."` is already on it.
     pub fn setup_python_path(py: Python<'_>) -> PyResult<()> {
-        let sys: Bound<'_, PyModule>= PyModule::import(py, "sys")?;
-        let path: Bound<'_, PyList> = sys.getattr("path")?.downcast_into::<PyList>()?;
-        
-        path.insert(0, PyString::new(py, "."))?;
+        PYTHON_PATH_READY.get_or_try_init(py, || {
+            let sys: Bound<'_, PyModule>= PyModule::import(py, "sys")?;
+            let path: Bound<'_, PyList> = sys.getattr("path")?.downcast_into::<PyList>()?;
+
+            if !path.contains(".")? {
+                path.insert(0, PyString::new(py, "."))?;
+            }
+
+            Ok::<(), PyErr>(())
+        })?;
+
         Ok(())
     }
 
-    pub async fn exe_python_callable_async(
-        py_func: Py<PyFunction>,
-        arg: Py<PyAny>
-    ) -> PyResult<()> {
+    /// A readable label for a callable, used to name the tasks that run it.
+    pub fn callable_name(py_func: &Bound<'_, PyAny>) -> String {
+        py_func
+            .getattr("__qualname__")
+            .and_then(|name: Bound<'_, PyAny>| name.extract::<String>())
+            .unwrap_or_else(|_| py_func.to_string())
+    }
+
+    /// How to call a callable, beyond the plain `py_func(arg)`.
+    #[derive(Default)]
+    pub struct CallOptions {
+        /// Keep every other coil callable from running alongside this one.
+        pub exclusive: bool,
+        /// Pass this handle (the task's own) as the first argument, as `py_func(handle, arg)`.
+        pub handle: Option<PyTaskHandle>,
+    }
+
+    async fn run_callable(py_func: Arc<Py<PyFunction>>, arg: Option<Arc<Py<PyAny>>>, handle: Option<PyTaskHandle>) -> PyResult<PyObject> {
+        let task_id: Option<u64> = task::scheduled_task_id();
+
         tokio::task::spawn_blocking(move || {
-            Python::with_gil(|py_blocking| {
-                py_func.call1(py_blocking, (arg,)).expect("Failed to call.");
+            let _running: task::RunningTaskGuard = task::enter_task(task_id);
+
+            // Both references are moved into the GIL scope and released there, so if they were the
+            // last ones their decref happens immediately instead of being parked in pyo3's
+            // pending-drop pool (which is only flushed the next time some thread takes the GIL).
+            gil::attach(move |py_blocking| {
+                hooks::prepare_thread(py_blocking);
+                hooks::task_started(py_blocking, task_id);
+
+                let result: PyResult<PyObject> = match (handle, arg) {
+                    (Some(handle), Some(arg)) => py_func.call1(py_blocking, (handle, arg.clone_ref(py_blocking))),
+                    (Some(handle), None) => py_func.call1(py_blocking, (handle,)),
+                    (None, Some(arg)) => py_func.call1(py_blocking, (arg.clone_ref(py_blocking),)),
+                    (None, None) => py_func.call0(py_blocking),
+                };
+                drop(py_func);
+
+                hooks::task_finished(py_blocking, task_id, &result);
+
+                result
             })
         })
         .await
-        .map_err(|e: tokio::task::JoinError| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("Blocking task panicked: {}", e)))?;
+        .map_err(task::join_error)??
+    }
 
-        Ok(())
+    /// Calls `py_func(arg)` on the blocking pool. Both are behind an `Arc` so that callers that
+    /// make the same call repeatedly can clone them without the GIL.
+    pub async fn exe_python_callable_async(
+        py_func: Arc<Py<PyFunction>>,
+        arg: Arc<Py<PyAny>>
+    ) -> PyResult<PyObject> {
+        exe_python_callable(py_func, Some(arg), CallOptions::default()).await
+    }
+
+    /// Like `exe_python_callable_async`, but calls `py_func()` with no argument.
+    pub async fn exe_python_callable0_async(py_func: Arc<Py<PyFunction>>) -> PyResult<PyObject> {
+        exe_python_callable(py_func, None, CallOptions::default()).await
+    }
+
+    pub async fn exe_python_callable(
+        py_func: Arc<Py<PyFunction>>,
+        arg: Option<Arc<Py<PyAny>>>,
+        options: CallOptions
+    ) -> PyResult<PyObject> {
+        if options.exclusive {
+            let _exclusive: exclusive::ExclusiveGuard = exclusive::exclusive().await;
+
+            run_callable(py_func, arg, options.handle).await
+        } else {
+            let _shared: exclusive::SharedGuard = exclusive::shared().await;
+
+            run_callable(py_func, arg, options.handle).await
+        }
     }
 }
 
+struct SpawnOptions {
+    priority: Priority,
+    exclusive: bool,
+    pass_handle: bool,
+    parent: Option<PyTaskHandle>,
+}
+
+fn spawn_thread(py: Python<'_>, py_func: Py<PyFunction>, arg: Py<PyAny>, options: SpawnOptions) -> PyResult<PyTaskHandle> {
+    spawn_thread_throttled(py, py_func, arg, options, None)
+}
+
+// `spawn_thread`, first taking a permit from `throttle`, if given, and holding it until the task is
+// done. It is taken before the task slot, so a task waiting on it doesn't hold a slot meanwhile.
+fn spawn_thread_throttled(
+    py: Python<'_>,
+    py_func: Py<PyFunction>,
+    arg: Py<PyAny>,
+    options: SpawnOptions,
+    throttle: Option<&PySemaphore>,
+) -> PyResult<PyTaskHandle> {
+    gil::check_available()?;
+    internal::setup_python_path(py)?;
+
+    let SpawnOptions { priority, exclusive, pass_handle, parent } = options;
+    let name: String = internal::callable_name(py_func.bind(py));
+    let throttled: Option<OwnedSemaphorePermit> = throttle.map(|semaphore| semaphore.acquire_owned(py)).transpose()?;
+    let slot: Option<OwnedSemaphorePermit> = limit::acquire(py)?;
+
+    let handle: PyTaskHandle = PyTaskHandle::spawn_with(name, move |handle: PyTaskHandle| async move {
+        let _throttled: Option<OwnedSemaphorePermit> = throttled;
+        let _slot: Option<OwnedSemaphorePermit> = slot;
+        let _permit: PriorityPermit = priority::acquire(priority).await;
+        let options: internal::CallOptions = internal::CallOptions {
+            exclusive,
+            handle: pass_handle.then_some(handle),
+        };
+
+        internal::exe_python_callable(Arc::new(py_func), Some(Arc::new(arg)), options).await
+    })?;
+
+    if let Some(parent) = parent {
+        parent.state().add_child(handle.state());
+    }
+
+    Ok(handle)
+}
+
+/// Runs `py_func(arg)` on the runtime's blocking pool. `priority` ("high", "normal" or "low")
+/// decides how much of the pool the call may compete for when it is under load.
+///
+/// Every callable holds the GIL while it runs, but Python still hands the GIL to other threads
+/// every few milliseconds, so callables normally interleave. With `exclusive=True` the call
+/// instead waits for every other coil callable to finish, and none start until it returns, so
+/// it can mutate shared state without being interleaved with other coil work (plain Python
+/// threads can still run). An exclusive callable must not wait on another coil callable, since
+/// that one can't start until it is done.
+///
+/// With `pass_handle=True` the callable is called as `py_func(handle, arg)`, getting its own
+/// `TaskHandle`, e.g. to check `handle.is_cancelled()` or to spawn its own successor.
+///
+/// With `parent`, the task is cancelled whenever that one is, so cancelling the handle at the
+/// root of a tree of tasks spawned this way cancels all of it.
+///
+/// Blocks while the cap set by `set_max_concurrent_tasks` is reached. Returns `None` instead of a
+/// handle if the cap was set to reject and every slot is taken; use `spawn_or_raise` to get that
+/// as `ExecutorSaturated` instead. Anything else that keeps the task from starting (the runtime
+/// having been shut down, `KeyboardInterrupt` while waiting for a slot) is raised either way.
+#[pyfunction]
+#[pyo3(signature = (py_func, arg, *, priority = "normal", exclusive = false, pass_handle = false, parent = None))]
+fn new_thread(
+    py: Python<'_>,
+    py_func: Py<PyFunction>,
+    arg: Py<PyAny>,
+    priority: &str,
+    exclusive: bool,
+    pass_handle: bool,
+    parent: Option<PyTaskHandle>,
+) -> PyResult<Option<PyTaskHandle>> {
+    let priority: Priority = Priority::parse(priority)?;
+
+    match spawn_thread(py, py_func, arg, SpawnOptions { priority, exclusive, pass_handle, parent }) {
+        Ok(handle) => Ok(Some(handle)),
+        Err(error) if error.is_instance_of::<errors::ExecutorSaturated>(py) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+/// Like `new_thread`, but raises if the task could not be accepted.
+#[pyfunction]
+#[pyo3(signature = (py_func, arg, *, priority = "normal", exclusive = false, pass_handle = false, parent = None))]
+fn spawn_or_raise(
+    py: Python<'_>,
+    py_func: Py<PyFunction>,
+    arg: Py<PyAny>,
+    priority: &str,
+    exclusive: bool,
+    pass_handle: bool,
+    parent: Option<PyTaskHandle>,
+) -> PyResult<PyTaskHandle> {
+    let priority: Priority = Priority::parse(priority)?;
+
+    spawn_thread(py, py_func, arg, SpawnOptions { priority, exclusive, pass_handle, parent })
+}
+
+/// Like `spawn_or_raise`, but returns an `asyncio.Future` on the running event loop, so the result
+/// can be awaited from a coroutine: `await new_thread_awaitable(fn, arg)`. Cancelling the future
+/// cancels the task. Raises `RuntimeError` when called outside a running event loop.
+#[pyfunction]
+#[pyo3(signature = (py_func, arg, *, priority = "normal", exclusive = false))]
+fn new_thread_awaitable(py: Python<'_>, py_func: Py<PyFunction>, arg: Py<PyAny>, priority: &str, exclusive: bool) -> PyResult<PyObject> {
+    let priority: Priority = Priority::parse(priority)?;
+    let options: SpawnOptions = SpawnOptions { priority, exclusive, pass_handle: false, parent: None };
+
+    // Looked up first, so nothing is spawned when there is no loop to report to.
+    let event_loop: Bound<'_, PyAny> = aio::running_loop(py)?;
+    let handle: PyTaskHandle = spawn_thread(py, py_func, arg, options)?;
+    let waited: PyTaskHandle = handle.clone();
+
+    let future: PyObject = aio::awaitable(event_loop, async move {
+        waited.state().wait().await;
+        move |py: Python<'_>| waited.state().output(py)
+    })?;
+
+    aio::on_cancelled(future.bind(py), move || {
+        handle.cancel();
+    })?;
+
+    Ok(future)
+}
+
+/// Like `spawn_or_raise`, but returns a `Future` that behaves like `concurrent.futures.Future`,
+/// for code written against `ThreadPoolExecutor.submit`.
 #[pyfunction]
-fn new_thread(py: Python<'_>, py_func: Py<PyFunction>, arg: Py<PyAny>) -> PyResult<()> {
+#[pyo3(signature = (py_func, arg, *, priority = "normal", exclusive = false))]
+fn submit(py: Python<'_>, py_func: Py<PyFunction>, arg: Py<PyAny>, priority: &str, exclusive: bool) -> PyResult<future::PyFuture> {
+    let priority: Priority = Priority::parse(priority)?;
+    let options: SpawnOptions = SpawnOptions { priority, exclusive, pass_handle: false, parent: None };
+
+    Ok(future::PyFuture::new(spawn_thread(py, py_func, arg, options)?))
+}
+
+/// Like `spawn_or_raise`, but first takes a permit from `semaphore` (blocking, with the GIL
+/// released, until one is free) and holds it until the task is done, however it ends.
+#[pyfunction]
+#[pyo3(signature = (py_func, arg, semaphore, *, priority = "normal"))]
+fn spawn_throttled(py: Python<'_>, py_func: Py<PyFunction>, arg: Py<PyAny>, semaphore: PySemaphore, priority: &str) -> PyResult<PyTaskHandle> {
+    let priority: Priority = Priority::parse(priority)?;
+    let options: SpawnOptions = SpawnOptions { priority, exclusive: false, pass_handle: false, parent: None };
+
+    spawn_thread_throttled(py, py_func, arg, options, Some(&semaphore))
+}
+
+/// Like `new_thread`, but the task is run under `tokio::task::unconstrained`, so it is never made to
+/// yield because it used up its cooperative budget. That can shave latency off a critical path,
+/// at the cost of fairness: while the task has work ready it keeps its worker thread to itself
+/// and can starve every other task scheduled there. Use it deliberately.
+#[pyfunction]
+fn run_unconstrained(py: Python<'_>, py_func: Py<PyFunction>, arg: Py<PyAny>) -> PyResult<PyTaskHandle> {
+    gil::check_available()?;
     internal::setup_python_path(py)?;
 
-    let _: JoinHandle<Result<(), PyErr>> = TOKIO_RUNTIME.spawn(internal::exe_python_callable_async(py_func, arg));
+    let name: String = internal::callable_name(py_func.bind(py));
 
-    Ok(())
+    PyTaskHandle::spawn(name, tokio::task::unconstrained(internal::exe_python_callable_async(Arc::new(py_func), Arc::new(arg))))
 }
 
+/// Calls `py_func()` once on each of the runtime's worker threads, blocking until every call has
+/// returned, e.g. to set up thread-local state in a native library. Tokio has no way to target a
+/// specific worker, so this spawns one task per worker and parks each on a barrier after its call,
+/// which keeps any worker from picking up a second one. That is best-effort: if some workers are
+/// tied up by long-running tasks this waits until they free up.
 #[pyfunction]
-fn fetch_metrics(py: Python<'_>) -> PyResult<Py<PyDict>> {
-    let py_dict: Bound<'_, PyDict> = PyDict::new(py);
-    let metrics = TOKIO_RUNTIME.metrics();
-    
-    py_dict.set_item("global_queue_depth", metrics.global_queue_depth())?;
-    py_dict.set_item("num_alive_tasks", metrics.num_alive_tasks())?;
-    py_dict.set_item("num_workers", metrics.num_workers())?;
+fn run_on_all_workers(py: Python<'_>, py_func: Py<PyFunction>) -> PyResult<()> {
+    let workers: usize = runtime::handle()?.metrics().num_workers();
+    let barrier: Arc<Barrier> = Arc::new(Barrier::new(workers));
+    let py_func: Arc<Py<PyFunction>> = Arc::new(py_func);
+
+    py.allow_threads(|| {
+        runtime::block_on(async {
+            let mut set: JoinSet<PyResult<()>> = JoinSet::new();
+
+            for _ in 0..workers {
+                let barrier: Arc<Barrier> = barrier.clone();
+                let py_func: Arc<Py<PyFunction>> = py_func.clone();
+
+                set.spawn(async move {
+                    let result: PyResult<()> = gil::attach(|py_worker| py_func.call0(py_worker).map(drop)).and_then(|result| result);
+
+                    // Deliberately blocks the worker thread; see above.
+                    barrier.wait();
+
+                    result
+                });
+            }
 
-    Ok(py_dict.unbind())
+            while let Some(joined) = set.join_next().await {
+                joined.map_err(|e: JoinError| {
+                    PyErr::new::<PyRuntimeError, _>(format!("Worker initializer panicked: {}", e))
+                })??;
+            }
+
+            Ok(())
+        })
+    })?
+}
+
+/// Returns the runtime's metrics as a dict. Besides the raw counts (`global_queue_depth`,
+/// `num_alive_tasks`, `num_workers`) it has:
+///
+/// - `worker_utilization`: the share of worker time spent busy since the runtime started.
+/// - `blocking_utilization`: the share of the blocking pool running callables. Tokio only
+///   exposes this with `--cfg tokio_unstable`; otherwise it is `None`.
+/// - `queue_pressure`: tasks waiting in the global queue per worker.
+/// - `sampled_at_ns`: when the reading was taken, on a monotonic clock.
+/// - `task_groups`: for each live `TaskGroup`, by name, how many of its tasks are `alive`,
+///   `finished` and `failed`.
+#[pyfunction]
+fn fetch_metrics(py: Python<'_>) -> PyResult<Py<PyDict>> {
+    Ok(MetricsSnapshot::capture()?.to_dict(py)?.unbind())
 }
 
+/// Returns the same metrics as `fetch_metrics` as a JSON object, with a `schema_version` (bumped
+/// on incompatible changes) and a wall-clock `timestamp` in seconds since the Unix epoch.
 #[pyfunction]
-fn wait_for_event(py: Python<'_>, arguments: Vec<i128>) -> PyResult<PyObject> {
-    match arguments[0] {
-        0x00 => {
-            assert_eq!(arguments.len(), 2);
+fn fetch_metrics_json() -> PyResult<String> {
+    MetricsSnapshot::capture()?.to_json()
+}
+
+/// A mutex over `tokio::sync::Mutex`: waiters are queued, and a release hands the lock straight to
+/// the one that has waited longest instead of letting whoever retries first take it.
+#[pyclass(name = "MutexLock")]
+#[derive(Clone)]
+pub struct PyMutexLock {
+    id: u64,
+    mutex: Arc<tokio::sync::Mutex<()>>,
+    // The guard and the thread that took it, while the lock is held.
+    held: Arc<Mutex<Option<HeldLock>>>,
+    strict: bool,
+}
+
+struct HeldLock {
+    _guard: OwnedMutexGuard<()>,
+    owner: ThreadId,
+}
+
+static NEXT_LOCK_ID: AtomicU64 = AtomicU64::new(1);
+
+impl Default for PyMutexLock {
+    fn default() -> Self {
+        Self {
+            id: NEXT_LOCK_ID.fetch_add(1, Ordering::Relaxed),
+            mutex: Arc::new(tokio::sync::Mutex::new(())),
+            held: Arc::new(Mutex::new(None)),
+            strict: true,
+        }
+    }
+}
+
+// How often `acquire_interruptible` stops waiting to check for signals.
+const SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+impl PyMutexLock {
+    // A snapshot that may be stale by the time the caller looks at it. A lock whose guard has just
+    // been handed over but not yet stored reads as unlocked for that moment.
+    pub fn is_locked(&self) -> bool {
+        self.held().is_some()
+    }
+
+    fn held(&self) -> MutexGuard<'_, Option<HeldLock>> {
+        self.held.lock().expect("Lock owner mutex was poisoned.")
+    }
+
+    // Guards are only ever stored from the thread that asked for the lock, after `block_on` (which
+    // polls on the calling thread) has returned, so that is the owner recorded.
+    fn hold(&self, guard: OwnedMutexGuard<()>) {
+        *self.held() = Some(HeldLock { _guard: guard, owner: std::thread::current().id() });
+    }
+
+    fn try_lock(&self) -> bool {
+        match self.mutex.clone().try_lock_owned() {
+            Ok(guard) => {
+                self.hold(guard);
+                true
+            }
+            Err(_) => false,
+        }
+    }
 
-            let time_duration: Duration = Duration::from_nanos(arguments[1] as u64);
-            
-            let sleep_task = TOKIO_RUNTIME.spawn(async move {
-                tokio::time::sleep(time_duration).await
-            });
+    // Cancel-safe: dropping the future before it is ready leaves the lock untouched and gives up
+    // its place in the queue.
+    async fn lock(&self) -> OwnedMutexGuard<()> {
+        self.mutex.clone().lock_owned().await
+    }
+
+    fn lock_until(&self, py: Python<'_>, deadline: tokio::time::Instant) -> PyResult<bool> {
+        let tracked: bool = deadlock::is_enabled();
 
-            let _ = TOKIO_RUNTIME.block_on(sleep_task)
-                .map_err(|e: tokio::task::JoinError| {
-                    PyErr::new::<PyRuntimeError, _>(
-                        format!("Failed to wait for event (task join error): {}", e)
-                    )
-                })?;
+        if tracked {
+            deadlock::check_acquire(py, self.id)?;
         }
-        other => {
-            return Err(PyErr::new::<PyValueError, _>(
-                format!("Unknown event id, '{other}'.")
-            ));
+
+        let s = self.clone();
+        let guard: Option<OwnedMutexGuard<()>> = py.allow_threads(move || {
+            runtime::block_on(async move { tokio::time::timeout_at(deadline, s.lock()).await.ok() })
+        })?;
+
+        let Some(guard) = guard else { return Ok(false) };
+
+        self.hold(guard);
+
+        if tracked {
+            deadlock::record_acquire(self.id);
         }
+
+        Ok(true)
     }
 
-    Ok(py.None())
+    // Dropping the guard wakes the next waiter in line, which then owns the lock.
+    fn unlock(&self) {
+        self.held().take();
+    }
 }
 
-#[pyclass(name = "MutexLock")]
-#[derive(Clone)]
-pub struct PyMutexLock {
-    locked: Arc<AtomicBool>,
-    notify: Arc<Notify>
+/// Waits until one of `locks` is free and takes it, returning its index. If several are free,
+/// the first of them is taken.
+#[pyfunction]
+fn wait_any_unlocked(py: Python<'_>, locks: Vec<PyMutexLock>) -> PyResult<usize> {
+    if locks.is_empty() {
+        return Err(PyErr::new::<PyValueError, _>("wait_any_unlocked needs at least one lock."));
+    }
+
+    let tracked: bool = deadlock::is_enabled();
+
+    if tracked {
+        for lock in &locks {
+            deadlock::check_acquire(py, lock.id)?;
+        }
+    }
+
+    let (index, guard): (usize, OwnedMutexGuard<()>) = py.allow_threads(|| {
+        runtime::block_on(async {
+            // Polled in order, so the first free lock wins. The others are dropped unfinished,
+            // which gives up their places in line without taking them.
+            let mut waits: Vec<Pin<Box<dyn Future<Output = OwnedMutexGuard<()>> + Send + '_>>> =
+                locks.iter().map(|lock| Box::pin(lock.lock()) as Pin<Box<dyn Future<Output = _> + Send>>).collect();
+
+            std::future::poll_fn(|cx| {
+                for (index, wait) in waits.iter_mut().enumerate() {
+                    if let Poll::Ready(guard) = wait.as_mut().poll(cx) {
+                        return Poll::Ready((index, guard));
+                    }
+                }
+
+                Poll::Pending
+            })
+            .await
+        })
+    })?;
+
+    locks[index].hold(guard);
+
+    if tracked {
+        deadlock::record_acquire(locks[index].id);
+    }
+
+    Ok(index)
+}
+
+/// Waits until every one of `locks` is free at once and takes them all. At most one of them is
+/// held while waiting, and it is let go before waiting on another, so this can't deadlock
+/// against another `acquire_all` over the same locks. Raises `ValueError` if a lock is passed
+/// more than once, since waiting for it to be free while holding it would never end.
+#[pyfunction]
+fn acquire_all(py: Python<'_>, locks: Vec<PyMutexLock>) -> PyResult<()> {
+    for (index, lock) in locks.iter().enumerate() {
+        if locks[..index].iter().any(|earlier: &PyMutexLock| earlier.id == lock.id) {
+            return Err(PyErr::new::<PyValueError, _>(format!("Lock {} was passed to acquire_all more than once.", lock.id)));
+        }
+    }
+
+    let tracked: bool = deadlock::is_enabled();
+
+    if tracked {
+        for lock in &locks {
+            deadlock::check_acquire(py, lock.id)?;
+        }
+    }
+
+    let guards: Vec<OwnedMutexGuard<()>> = py.allow_threads(|| {
+        runtime::block_on(async {
+            // The lock that was busy last time around, taken by waiting in its queue.
+            let mut waited: Option<(usize, OwnedMutexGuard<()>)> = None;
+
+            loop {
+                let mut guards: Vec<OwnedMutexGuard<()>> = Vec::with_capacity(locks.len());
+                let mut busy: Option<usize> = None;
+
+                // Takes the locks in order, stopping at the first one that isn't free.
+                for (index, lock) in locks.iter().enumerate() {
+                    if let Some((waited_index, _)) = &waited
+                        && *waited_index == index
+                    {
+                        guards.push(waited.take().expect("The waited-for lock was just checked.").1);
+                        continue
+                    }
+
+                    match lock.mutex.clone().try_lock_owned() {
+                        Ok(guard) => guards.push(guard),
+                        Err(_) => {
+                            busy = Some(index);
+                            break
+                        }
+                    }
+                }
+
+                let Some(busy) = busy else { break guards };
+
+                // Back out of the ones already taken, then wait for the one that wasn't free.
+                drop(guards);
+                drop(waited.take());
+                waited = Some((busy, locks[busy].lock().await));
+            }
+        })
+    })?;
+
+    for (lock, guard) in locks.iter().zip(guards) {
+        lock.hold(guard);
+    }
+
+    if tracked {
+        for lock in &locks {
+            deadlock::record_acquire(lock.id);
+        }
+    }
+
+    Ok(())
 }
 
 #[pymethods]
 impl PyMutexLock {
+    /// With `strict` (the default), `release` raises `LockError` unless the calling thread holds
+    /// the lock. Without it, releasing an unlocked lock does nothing and any thread may release
+    /// a held one.
     #[new]
-    fn new() -> Self {
-        Self {
-            locked: Arc::new(AtomicBool::new(false)),
-            notify: Arc::new(Notify::new()),
-        }
+    #[pyo3(signature = (*, strict = true))]
+    fn new(strict: bool) -> Self {
+        Self { strict, ..Self::default() }
     }
 
     pub fn acquire(&self, py: Python<'_>) -> PyResult<()> {
         let s = self.clone();
+        let tracked: bool = deadlock::is_enabled();
 
-        py.allow_threads(move || {
-            TOKIO_RUNTIME.block_on(async move {
-                loop {
-                    if s.locked.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
-                        break
-                    }
+        if tracked {
+            deadlock::check_acquire(py, self.id)?;
+        }
 
-                    s.notify.notified().await;
-                }
-            });
-        });
+        let guard: OwnedMutexGuard<()> = py.allow_threads(move || runtime::block_on(async move { s.lock().await }))?;
+
+        self.hold(guard);
+
+        if tracked {
+            deadlock::record_acquire(self.id);
+        }
 
         Ok(())
     }
 
+    /// Like `acquire`, but checks for Python signals while waiting, returning `True` once the
+    /// lock is held and `False` if a signal arrived first. The exception the signal's handler
+    /// raised (`KeyboardInterrupt` for Ctrl-C) is discarded rather than propagated.
+    pub fn acquire_interruptible(&self, py: Python<'_>) -> PyResult<bool> {
+        let tracked: bool = deadlock::is_enabled();
+
+        if tracked {
+            deadlock::check_acquire(py, self.id)?;
+        }
+
+        loop {
+            let s = self.clone();
+            let guard: Option<OwnedMutexGuard<()>> = py.allow_threads(move || {
+                runtime::block_on(async move { tokio::time::timeout(SIGNAL_CHECK_INTERVAL, s.lock()).await.ok() })
+            })?;
+
+            if let Some(guard) = guard {
+                self.hold(guard);
+                break
+            }
+
+            if py.check_signals().is_err() {
+                return Ok(false);
+            }
+        }
+
+        if tracked {
+            deadlock::record_acquire(self.id);
+        }
+
+        Ok(true)
+    }
+
+    /// Takes the lock only if it is free right now, returning whether it did. Never waits, so it
+    /// jumps ahead of anyone queued only when nobody holds the lock.
+    pub fn try_acquire(&self) -> bool {
+        let acquired: bool = self.try_lock();
+
+        if acquired && deadlock::is_enabled() {
+            deadlock::record_acquire(self.id);
+        }
+
+        acquired
+    }
+
+    /// Like `acquire`, but gives up after `duration_ns`, returning whether the lock was taken.
+    pub fn try_acquire_for(&self, py: Python<'_>, duration_ns: u64) -> PyResult<bool> {
+        self.lock_until(py, tokio::time::Instant::now() + Duration::from_nanos(duration_ns))
+    }
+
+    /// Like `try_acquire_for`, but gives up at the absolute `instant_ns` on `monotonic_ns`'s
+    /// clock, so several waits can share one overall deadline. Returns `False` straight away if
+    /// the lock is taken and the deadline has already passed.
+    pub fn try_acquire_until(&self, py: Python<'_>, instant_ns: u64) -> PyResult<bool> {
+        self.lock_until(py, runtime::instant_at(instant_ns))
+    }
+
     pub fn release(&self, _py: Python<'_>) -> PyResult<()> {
-        self.locked.store(false, Ordering::SeqCst);
+        let owner: Option<ThreadId> = self.held().as_ref().map(|held: &HeldLock| held.owner);
 
-        self.notify.notify_one();
+        if self.strict {
+            match owner {
+                None => return Err(PyErr::new::<LockError, _>(format!("MutexLock #{} is not locked.", self.id))),
+                Some(owner) if owner != std::thread::current().id() => {
+                    return Err(PyErr::new::<LockError, _>(format!("MutexLock #{} is held by another thread.", self.id)));
+                }
+                Some(_) => (),
+            }
+        } else if !self.is_locked() {
+            return Ok(());
+        }
+
+        if deadlock::is_enabled() {
+            deadlock::record_release(self.id);
+        }
+
+        self.unlock();
         Ok(())
     }
     
+    /// Whether the calling thread is the one holding the lock.
+    pub fn is_owned(&self) -> bool {
+        self.held().as_ref().is_some_and(|held: &HeldLock| held.owner == std::thread::current().id())
+    }
+
+    #[getter]
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
     pub fn get_locked(&self, py: Python<'_>) -> Py<PyBool> {
-        <pyo3::Bound<'_, PyBool> as Clone>::clone(&PyBool::new(py, self.locked.load(Ordering::SeqCst))).unbind()
+        <pyo3::Bound<'_, PyBool> as Clone>::clone(&PyBool::new(py, self.is_locked())).unbind()
+    }
+
+    fn __enter__(&self, py: Python<'_>) -> PyResult<()> {
+        self.acquire(py)
+    }
+
+    fn __exit__(
+        &self,
+        py: Python<'_>,
+        _exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        self.release(py)?;
+
+        Ok(false)
     }
 }
 
 #[pymodule]
-fn coil_core(_py: Python, m: Bound<PyModule>) -> PyResult<()> {
+fn coil_core(py: Python, m: Bound<PyModule>) -> PyResult<()> {
     pyo3::prepare_freethreaded_python();
+
+    // `atexit` runs hooks last-registered first, so the runtime is shut down (running the shutdown
+    // hooks) and then the log flushed, all before coil's threads stop taking the GIL.
+    let atexit: Bound<'_, PyModule> = PyModule::import(py, "atexit")?;
+    atexit.call_method1("register", (wrap_pyfunction!(gil::mark_finalizing, &m)?,))?;
+    atexit.call_method1("register", (wrap_pyfunction!(log::flush_log, &m)?,))?;
+    atexit.call_method1("register", (wrap_pyfunction!(runtime::shutdown_at_exit, &m)?,))?;
+
+    let os: Bound<'_, PyModule> = PyModule::import(py, "os")?;
+
+    if os.hasattr("register_at_fork")? {
+        let kwargs: Bound<'_, PyDict> = PyDict::new(py);
+        kwargs.set_item("before", wrap_pyfunction!(runtime::before_fork, &m)?)?;
+        kwargs.set_item("after_in_parent", wrap_pyfunction!(runtime::after_fork_in_parent, &m)?)?;
+        kwargs.set_item("after_in_child", wrap_pyfunction!(runtime::reset_after_fork, &m)?)?;
+        os.call_method("register_at_fork", (), Some(&kwargs))?;
+    }
     
     m.add_function(wrap_pyfunction!(new_thread, &m)?)?;
+    m.add_function(wrap_pyfunction!(spawn_or_raise, &m)?)?;
+    m.add_function(wrap_pyfunction!(submit, &m)?)?;
+    m.add_function(wrap_pyfunction!(new_thread_awaitable, &m)?)?;
+    m.add_function(wrap_pyfunction!(schedule::schedule_once, &m)?)?;
+    m.add_function(wrap_pyfunction!(schedule::schedule_interval, &m)?)?;
+    m.add_function(wrap_pyfunction!(spawn_throttled, &m)?)?;
+    m.add_function(wrap_pyfunction!(run_unconstrained, &m)?)?;
+    m.add_function(wrap_pyfunction!(run_on_all_workers, &m)?)?;
+    m.add_function(wrap_pyfunction!(watchdog::call_with_hard_timeout, &m)?)?;
     m.add_function(wrap_pyfunction!(fetch_metrics, &m)?)?;
-    m.add_function(wrap_pyfunction!(wait_for_event, &m)?)?;
+    m.add_function(wrap_pyfunction!(fetch_metrics_json, &m)?)?;
+    m.add_function(wrap_pyfunction!(cache::cached, &m)?)?;
+    m.add_function(wrap_pyfunction!(metrics::enable_metrics_history, &m)?)?;
+    m.add_function(wrap_pyfunction!(metrics::metrics_history, &m)?)?;
+    m.add_function(wrap_pyfunction!(wait_any_unlocked, &m)?)?;
+    m.add_function(wrap_pyfunction!(acquire_all, &m)?)?;
+    m.add_function(wrap_pyfunction!(queue::spawn_generator, &m)?)?;
+    m.add_function(wrap_pyfunction!(queue::try_select_queues, &m)?)?;
+    m.add_function(wrap_pyfunction!(fs::read_file, &m)?)?;
+    m.add_function(wrap_pyfunction!(fs::write_file, &m)?)?;
+    m.add_function(wrap_pyfunction!(events::wait_for_event, &m)?)?;
+    m.add_function(wrap_pyfunction!(events::wait_any, &m)?)?;
+    m.add_function(wrap_pyfunction!(events::sleep, &m)?)?;
+    m.add_function(wrap_pyfunction!(deadlock::enable_deadlock_detection, &m)?)?;
+    m.add_function(wrap_pyfunction!(panic::set_panic_policy, &m)?)?;
+    m.add_function(wrap_pyfunction!(errors::set_exception_handler, &m)?)?;
+    m.add_function(wrap_pyfunction!(limit::set_max_concurrent_tasks, &m)?)?;
+    m.add_function(wrap_pyfunction!(hooks::set_thread_init, &m)?)?;
+    m.add_function(wrap_pyfunction!(hooks::on_task_start, &m)?)?;
+    m.add_function(wrap_pyfunction!(hooks::on_task_finish, &m)?)?;
+    m.add_function(wrap_pyfunction!(runtime::configure_runtime, &m)?)?;
+    m.add_function(wrap_pyfunction!(runtime::init, &m)?)?;
+    m.add_function(wrap_pyfunction!(runtime::runtime_context, &m)?)?;
+    m.add_function(wrap_pyfunction!(runtime::register_shutdown_hook, &m)?)?;
+    m.add_function(wrap_pyfunction!(runtime::shutdown, &m)?)?;
+    m.add_function(wrap_pyfunction!(runtime::reset_after_fork, &m)?)?;
+    m.add_function(wrap_pyfunction!(runtime::wait_until_idle, &m)?)?;
+    m.add_function(wrap_pyfunction!(runtime::monotonic_ns, &m)?)?;
+    m.add_function(wrap_pyfunction!(task::gather, &m)?)?;
+    m.add_function(wrap_pyfunction!(task::try_gather, &m)?)?;
+    m.add_function(wrap_pyfunction!(task::task_dump, &m)?)?;
+    m.add_function(wrap_pyfunction!(task::py_current_task_id, &m)?)?;
+    m.add_function(wrap_pyfunction!(log::log, &m)?)?;
+    m.add_function(wrap_pyfunction!(log::flush_log, &m)?)?;
+
+    m.add("TaskTimeout", py.get_type::<errors::TaskTimeout>())?;
+    m.add("TaskPanicked", py.get_type::<errors::TaskPanicked>())?;
+    m.add("TaskCancelled", py.get_type::<errors::TaskCancelled>())?;
+    m.add("QueueClosed", py.get_type::<errors::QueueClosed>())?;
+    m.add("CircuitOpen", py.get_type::<errors::CircuitOpen>())?;
+    m.add("BulkheadFull", py.get_type::<errors::BulkheadFull>())?;
+    m.add("ExecutorSaturated", py.get_type::<errors::ExecutorSaturated>())?;
+    m.add("LockError", py.get_type::<errors::LockError>())?;
 
+    m.add_class::<local::PyLocalExecutor>()?;
     m.add_class::<PyMutexLock>()?;
+    m.add_class::<breaker::PyCircuitBreaker>()?;
+    m.add_class::<bulkhead::PyBulkhead>()?;
+    m.add_class::<cache::PyTtlCache>()?;
+    m.add_class::<cache::PyCachedFunction>()?;
+    m.add_class::<channel::PyChannel>()?;
+    m.add_class::<events::PyEvent>()?;
+    m.add_class::<flight::PySingleFlight>()?;
+    m.add_class::<future::PyFuture>()?;
+    m.add_class::<group::PyTaskGroup>()?;
+    m.add_class::<pool::PyWorkerPool>()?;
+    m.add_class::<queue::PyPriorityQueue>()?;
+    m.add_class::<pubsub::PyPubSub>()?;
+    m.add_class::<pubsub::PySubscription>()?;
+    m.add_class::<queue::PyQueue>()?;
+    m.add_class::<runtime::PyRuntimeContext>()?;
+    m.add_class::<schedule::PyScheduledTask>()?;
+    m.add_class::<sync::PyAtomicCounter>()?;
+    m.add_class::<sync::PyBarrier>()?;
+    m.add_class::<sync::PyCondition>()?;
+    m.add_class::<sync::PyRwLock>()?;
+    m.add_class::<sync::PyRwLockGuard>()?;
+    m.add_class::<PySemaphore>()?;
+    m.add_class::<sync::PyShardedLock>()?;
+    m.add_class::<sync::PyShardGuard>()?;
+    m.add_class::<sync::PySpinLock>()?;
+    m.add_class::<PyTaskHandle>()?;
+    m.add_class::<timer::PyResettableTimer>()?;
 
     Ok(())
 }
\ No newline at end of file
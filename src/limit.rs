@@ -0,0 +1,82 @@
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use tokio::sync::{AcquireError, OwnedSemaphorePermit, Semaphore, TryAcquireError};
+
+use crate::errors::ExecutorSaturated;
+use crate::runtime;
+
+struct TaskLimit {
+    slots: Arc<Semaphore>,
+    max: usize,
+    reject: bool,
+}
+
+// Replaced wholesale when the limit changes; tasks already running keep the permit they took from
+// the old semaphore, so they count against the limit they started under.
+static TASK_LIMIT: Lazy<Mutex<Option<TaskLimit>>> = Lazy::new(|| Mutex::new(None));
+
+/// Refills the task limit, keeping its size. Only for a forked child, where the tasks holding
+/// slots don't exist and would never give them back.
+pub fn reset() {
+    if let Some(limit) = TASK_LIMIT.lock().expect("Task limit mutex was poisoned.").as_mut() {
+        std::mem::forget(std::mem::replace(&mut limit.slots, Arc::new(Semaphore::new(limit.max))));
+    }
+}
+
+/// Takes a slot for a new task, if there is a limit: waits for one with the GIL released, or
+/// raises `ExecutorSaturated` if the limit was set with `reject`. The task holds the permit until
+/// it is done.
+pub fn acquire(py: Python<'_>) -> PyResult<Option<OwnedSemaphorePermit>> {
+    let (slots, max, reject): (Arc<Semaphore>, usize, bool) = match TASK_LIMIT.lock().expect("Task limit mutex was poisoned.").as_ref() {
+        Some(limit) => (limit.slots.clone(), limit.max, limit.reject),
+        None => return Ok(None),
+    };
+
+    if reject {
+        return match slots.try_acquire_owned() {
+            Ok(permit) => Ok(Some(permit)),
+            Err(TryAcquireError::NoPermits) => Err(PyErr::new::<ExecutorSaturated, _>(
+                format!("All {max} task slots are in use.")
+            )),
+            Err(TryAcquireError::Closed) => Err(PyErr::new::<PyRuntimeError, _>("The task limit semaphore was closed.")),
+        };
+    }
+
+    py.allow_threads(move || runtime::block_on(slots.acquire_owned()))?
+        .map(Some)
+        .map_err(|e: AcquireError| PyErr::new::<PyRuntimeError, _>(format!("Failed to acquire a task slot: {}", e)))
+}
+
+/// Like `acquire`, but for work coil starts on its own, like a scheduled run: always waits for a
+/// slot, since there is no caller to hand `ExecutorSaturated` to.
+pub async fn wait_for_slot() -> PyResult<Option<OwnedSemaphorePermit>> {
+    let slots: Arc<Semaphore> = match TASK_LIMIT.lock().expect("Task limit mutex was poisoned.").as_ref() {
+        Some(limit) => limit.slots.clone(),
+        None => return Ok(None),
+    };
+
+    slots
+        .acquire_owned()
+        .await
+        .map(Some)
+        .map_err(|e: AcquireError| PyErr::new::<PyRuntimeError, _>(format!("Failed to acquire a task slot: {}", e)))
+}
+
+/// Caps how many tasks started with `new_thread` or `spawn_or_raise` can be in flight at once,
+/// counting from when they are spawned until they finish, whether or not they have made it onto
+/// the blocking pool yet. Spawning over the cap blocks (with the GIL released) until a task
+/// finishes, or, with `reject=True`, fails straight away: `spawn_or_raise` raises
+/// `ExecutorSaturated` and `new_thread` returns `None`. Scheduled runs count too, and always wait
+/// for a slot. `None` removes the cap.
+#[pyfunction]
+#[pyo3(signature = (n, *, reject = false))]
+pub fn set_max_concurrent_tasks(n: Option<usize>, reject: bool) {
+    *TASK_LIMIT.lock().expect("Task limit mutex was poisoned.") = n.map(|max| TaskLimit {
+        slots: Arc::new(Semaphore::new(max)),
+        max,
+        reject,
+    });
+}
@@ -0,0 +1,163 @@
+use std::sync::{Mutex, MutexGuard};
+use std::thread::JoinHandle;
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyFunction, PyTuple};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::LocalSet;
+
+use crate::errors::TaskCancelled;
+use crate::gil;
+use crate::hooks;
+use crate::internal;
+use crate::task::{self, PyTaskHandle, TaskOutput};
+
+struct LocalJob {
+    task_id: u64,
+    py_func: Py<PyFunction>,
+    args: Py<PyTuple>,
+    done: oneshot::Sender<TaskOutput>,
+}
+
+impl LocalJob {
+    fn run(self) {
+        // The handle was cancelled (or dropped along with its task) before the job came up. The
+        // job is still dropped under the GIL, so its references are released right away.
+        if self.done.is_closed() {
+            let _ = gil::attach(move |_| drop(self));
+            return
+        }
+
+        let LocalJob { task_id, py_func, args, done } = self;
+        let _running: task::RunningTaskGuard = task::enter_task(Some(task_id));
+
+        let result: PyResult<TaskOutput> = gil::attach(move |py| {
+            hooks::prepare_thread(py);
+            hooks::task_started(py, Some(task_id));
+
+            let result: TaskOutput = py_func.call1(py, args.bind(py));
+
+            hooks::task_finished(py, Some(task_id), &result);
+
+            result
+        });
+
+        let _ = done.send(result.and_then(|result| result));
+    }
+}
+
+fn run_local(mut jobs: mpsc::UnboundedReceiver<LocalJob>) {
+    let runtime: tokio::runtime::Runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(error) => {
+            let _ = gil::attach(|py| PyErr::new::<PyRuntimeError, _>(format!("Failed to start the local executor: {}", error)).print(py));
+            return
+        }
+    };
+    let local: LocalSet = LocalSet::new();
+
+    local.block_on(&runtime, async {
+        while let Some(job) = jobs.recv().await {
+            tokio::task::spawn_local(async move { job.run() });
+        }
+    });
+
+    // Work already handed over still runs before the thread exits.
+    runtime.block_on(local);
+}
+
+struct Running {
+    jobs: mpsc::UnboundedSender<LocalJob>,
+    thread: JoinHandle<()>,
+}
+
+/// Runs callables one at a time on a single thread of its own, through a tokio `LocalSet`, so
+/// every task it is given shares that thread: Python state that must not migrate between
+/// threads stays put, and consecutive tasks don't hand the GIL across threads. A callable blocks
+/// the executor for as long as it runs.
+#[pyclass(name = "LocalExecutor")]
+pub struct PyLocalExecutor {
+    running: Mutex<Option<Running>>,
+}
+
+impl PyLocalExecutor {
+    fn running(&self) -> MutexGuard<'_, Option<Running>> {
+        self.running.lock().expect("Local executor mutex was poisoned.")
+    }
+}
+
+#[pymethods]
+impl PyLocalExecutor {
+    #[new]
+    fn new() -> Self {
+        Self { running: Mutex::new(None) }
+    }
+
+    /// Starts the executor's thread. Raises if it is already running.
+    pub fn run(&self) -> PyResult<()> {
+        let mut running: MutexGuard<'_, Option<Running>> = self.running();
+
+        if running.is_some() {
+            return Err(PyErr::new::<PyRuntimeError, _>("The local executor is already running."));
+        }
+
+        let (jobs, receiver) = mpsc::unbounded_channel::<LocalJob>();
+        let thread: JoinHandle<()> = std::thread::Builder::new()
+            .name("coil-local".to_string())
+            .spawn(move || run_local(receiver))
+            .map_err(|e: std::io::Error| PyErr::new::<PyRuntimeError, _>(format!("Failed to start the local executor: {}", e)))?;
+
+        *running = Some(Running { jobs, thread });
+
+        Ok(())
+    }
+
+    /// Stops accepting work and waits, with the GIL released, for what was already spawned to
+    /// finish. Does nothing if the executor isn't running; it can be started again afterwards.
+    pub fn stop(&self, py: Python<'_>) -> PyResult<()> {
+        let Some(Running { jobs, thread }) = self.running().take() else { return Ok(()) };
+
+        if thread.thread().id() == std::thread::current().id() {
+            return Err(PyErr::new::<PyRuntimeError, _>("A local executor can't be stopped from one of its own tasks."));
+        }
+
+        drop(jobs);
+
+        py.allow_threads(move || thread.join())
+            .map_err(|_| PyErr::new::<PyRuntimeError, _>("The local executor's thread panicked."))
+    }
+
+    /// Queues `py_func(*args)` to run on the executor's thread. The returned handle works like
+    /// any other; cancelling it before the call starts keeps it from running at all.
+    #[pyo3(signature = (py_func, args = None))]
+    pub fn spawn_local(&self, py: Python<'_>, py_func: Py<PyFunction>, args: Option<Py<PyTuple>>) -> PyResult<PyTaskHandle> {
+        let jobs: mpsc::UnboundedSender<LocalJob> = match self.running().as_ref() {
+            Some(running) => running.jobs.clone(),
+            None => return Err(PyErr::new::<PyRuntimeError, _>("The local executor is not running.")),
+        };
+
+        let args: Py<PyTuple> = args.unwrap_or_else(|| PyTuple::empty(py).unbind());
+        let name: String = internal::callable_name(py_func.bind(py));
+
+        PyTaskHandle::spawn_with(name, move |handle: PyTaskHandle| {
+            let (done, outcome) = oneshot::channel::<TaskOutput>();
+            let job: LocalJob = LocalJob { task_id: handle.id(), py_func, args, done };
+            let queued: bool = jobs.send(job).is_ok();
+
+            async move {
+                if !queued {
+                    return Err(PyErr::new::<PyRuntimeError, _>("The local executor has stopped."));
+                }
+
+                outcome
+                    .await
+                    .unwrap_or_else(|_| Err(PyErr::new::<TaskCancelled, _>("The local executor stopped before running the task.")))
+            }
+        })
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running().is_some()
+    }
+}
@@ -0,0 +1,114 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::PyModule;
+
+use crate::gil;
+use crate::task;
+
+enum LogMessage {
+    Line(String),
+    Flush(Sender<()>),
+}
+
+// Lines are handed to a single writer thread, so callers never wait on stdout (or on each other)
+// and every line reaches `sys.stdout` in one `write` call, which is what keeps them from interleaving.
+static LOG_SENDER: Lazy<Mutex<Option<Sender<LogMessage>>>> = Lazy::new(|| Mutex::new(None));
+
+fn write_lines(py: Python<'_>, lines: &str) -> PyResult<()> {
+    let stdout: Bound<'_, PyAny> = PyModule::import(py, "sys")?.getattr("stdout")?;
+
+    stdout.call_method1("write", (lines,))?;
+    stdout.call_method0("flush")?;
+
+    Ok(())
+}
+
+fn run_writer(receiver: Receiver<LogMessage>) {
+    while let Ok(message) = receiver.recv() {
+        let mut lines: String = String::new();
+        let mut flushed: Vec<Sender<()>> = Vec::new();
+
+        // Take whatever else is already queued so a burst costs one GIL acquisition.
+        for message in std::iter::once(message).chain(receiver.try_iter()) {
+            match message {
+                LogMessage::Line(line) => lines.push_str(&line),
+                LogMessage::Flush(done) => flushed.push(done),
+            }
+        }
+
+        if !lines.is_empty() {
+            // Lines logged while the interpreter is going away have nowhere to go.
+            let _ = gil::attach(|py| {
+                if let Err(error) = write_lines(py, &lines) {
+                    error.print(py);
+                }
+            });
+        }
+
+        for done in flushed {
+            let _ = done.send(());
+        }
+    }
+}
+
+fn send(message: LogMessage) -> PyResult<()> {
+    let mut sender = LOG_SENDER.lock().expect("Log sender mutex was poisoned.");
+
+    if sender.is_none() {
+        let (new_sender, receiver) = mpsc::channel::<LogMessage>();
+
+        std::thread::Builder::new()
+            .name("coil-log".to_string())
+            .spawn(move || run_writer(receiver))
+            .map_err(|e: std::io::Error| PyErr::new::<PyRuntimeError, _>(format!("Failed to start the log writer: {}", e)))?;
+
+        *sender = Some(new_sender);
+    }
+
+    sender
+        .as_ref()
+        .expect("Log sender was just created.")
+        .send(message)
+        .map_err(|_| PyErr::new::<PyRuntimeError, _>("The log writer has stopped."))
+}
+
+/// Drops the channel to the writer thread, which a forked child doesn't have, so the next line
+/// starts a new one.
+pub fn reset() {
+    std::mem::forget(LOG_SENDER.lock().expect("Log sender mutex was poisoned.").take());
+}
+
+/// Writes `message` to `sys.stdout` as one whole line, without interleaving with other `log`
+/// calls. The write happens asynchronously on a dedicated thread; use `flush_log` to wait for it.
+/// With `with_task_id`, the line is prefixed with the id of the calling coil task.
+#[pyfunction]
+#[pyo3(signature = (message, *, with_task_id = false))]
+pub fn log(message: &str, with_task_id: bool) -> PyResult<()> {
+    let line: String = match (with_task_id, task::current_task_id()) {
+        (true, Some(id)) => format!("[task {id}] {message}\n"),
+        (true, None) => format!("[main] {message}\n"),
+        (false, _) => format!("{message}\n"),
+    };
+
+    send(LogMessage::Line(line))
+}
+
+/// Blocks until every line logged before this call has been written. Also runs at interpreter
+/// exit, so nothing logged is lost.
+#[pyfunction]
+pub fn flush_log(py: Python<'_>) -> PyResult<()> {
+    if LOG_SENDER.lock().expect("Log sender mutex was poisoned.").is_none() {
+        return Ok(());
+    }
+
+    let (done, flushed) = mpsc::channel::<()>();
+
+    send(LogMessage::Flush(done))?;
+
+    py.allow_threads(move || flushed.recv())
+        .map_err(|_| PyErr::new::<PyRuntimeError, _>("The log writer has stopped."))
+}
@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use tokio::runtime::RuntimeMetrics;
+use serde_json::{json, Map, Value};
+use tokio::task::AbortHandle;
+
+use crate::group::{self, GroupCounts};
+use crate::runtime;
+
+/// One reading of the runtime's metrics, with the derived ratios computed from it.
+pub struct MetricsSnapshot {
+    /// When the reading was taken, on `runtime::now_ns`'s clock.
+    pub sampled_at_ns: u64,
+    pub global_queue_depth: usize,
+    pub num_alive_tasks: usize,
+    pub num_workers: usize,
+    /// Share of the workers' time spent busy since the runtime started, from 0.0 to 1.0.
+    pub worker_utilization: f64,
+    /// Share of the blocking pool running callables. Needs `--cfg tokio_unstable`.
+    pub blocking_utilization: Option<f64>,
+    /// Tasks waiting in the global queue per worker.
+    pub queue_pressure: f64,
+    /// Task counts for each live `TaskGroup`, by name.
+    pub task_groups: Vec<(String, GroupCounts)>,
+}
+
+// Bumped whenever a field of `to_json`'s output is renamed, removed or changes meaning.
+const METRICS_SCHEMA_VERSION: u32 = 1;
+
+fn ratio(part: f64, whole: f64) -> f64 {
+    if whole > 0.0 { part / whole } else { 0.0 }
+}
+
+#[cfg(tokio_unstable)]
+fn blocking_utilization(metrics: &RuntimeMetrics) -> Option<f64> {
+    let busy: usize = metrics.num_blocking_threads().saturating_sub(metrics.num_idle_blocking_threads());
+
+    Some(ratio(busy as f64, runtime::max_blocking_threads() as f64))
+}
+
+#[cfg(not(tokio_unstable))]
+fn blocking_utilization(_metrics: &RuntimeMetrics) -> Option<f64> {
+    None
+}
+
+impl MetricsSnapshot {
+    pub fn capture() -> PyResult<Self> {
+        let metrics: RuntimeMetrics = runtime::handle()?.metrics();
+        let num_workers: usize = metrics.num_workers();
+        let uptime: Duration = runtime::uptime();
+        let busy: Duration = (0..num_workers).map(|worker| metrics.worker_total_busy_duration(worker)).sum();
+
+        Ok(Self {
+            sampled_at_ns: runtime::now_ns(),
+            global_queue_depth: metrics.global_queue_depth(),
+            num_alive_tasks: metrics.num_alive_tasks(),
+            num_workers,
+            worker_utilization: ratio(busy.as_secs_f64(), uptime.as_secs_f64() * num_workers as f64).min(1.0),
+            blocking_utilization: blocking_utilization(&metrics),
+            queue_pressure: ratio(metrics.global_queue_depth() as f64, num_workers as f64),
+            task_groups: group::counts(),
+        })
+    }
+
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let py_dict: Bound<'py, PyDict> = PyDict::new(py);
+
+        py_dict.set_item("sampled_at_ns", self.sampled_at_ns)?;
+        py_dict.set_item("global_queue_depth", self.global_queue_depth)?;
+        py_dict.set_item("num_alive_tasks", self.num_alive_tasks)?;
+        py_dict.set_item("num_workers", self.num_workers)?;
+        py_dict.set_item("worker_utilization", self.worker_utilization)?;
+        py_dict.set_item("blocking_utilization", self.blocking_utilization)?;
+        py_dict.set_item("queue_pressure", self.queue_pressure)?;
+
+        let task_groups: Bound<'py, PyDict> = PyDict::new(py);
+
+        for (name, counts) in &self.task_groups {
+            let group_dict: Bound<'py, PyDict> = PyDict::new(py);
+
+            group_dict.set_item("alive", counts.alive)?;
+            group_dict.set_item("finished", counts.finished)?;
+            group_dict.set_item("failed", counts.failed)?;
+            task_groups.set_item(name, group_dict)?;
+        }
+
+        py_dict.set_item("task_groups", task_groups)?;
+
+        Ok(py_dict)
+    }
+
+    /// The same fields as `to_dict`, plus `schema_version` and a wall-clock `timestamp` in
+    /// seconds since the Unix epoch.
+    pub fn to_json(&self) -> PyResult<String> {
+        let timestamp: f64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| PyErr::new::<PyRuntimeError, _>(format!("The system clock is before the Unix epoch: {}", e)))?
+            .as_secs_f64();
+
+        let task_groups: Map<String, Value> = self
+            .task_groups
+            .iter()
+            .map(|(name, counts)| {
+                let counts: Value = json!({ "alive": counts.alive, "finished": counts.finished, "failed": counts.failed });
+                (name.clone(), counts)
+            })
+            .collect();
+
+        let value: Value = json!({
+            "schema_version": METRICS_SCHEMA_VERSION,
+            "timestamp": timestamp,
+            "sampled_at_ns": self.sampled_at_ns,
+            "global_queue_depth": self.global_queue_depth,
+            "num_alive_tasks": self.num_alive_tasks,
+            "num_workers": self.num_workers,
+            "worker_utilization": self.worker_utilization,
+            "blocking_utilization": self.blocking_utilization,
+            "queue_pressure": self.queue_pressure,
+            "task_groups": task_groups,
+        });
+
+        Ok(value.to_string())
+    }
+}
+
+struct MetricsHistory {
+    samples: Mutex<VecDeque<MetricsSnapshot>>,
+    capacity: usize,
+}
+
+impl MetricsHistory {
+    fn record(&self, snapshot: MetricsSnapshot) {
+        let mut samples = self.samples.lock().expect("Metrics history mutex was poisoned.");
+
+        if samples.len() == self.capacity {
+            samples.pop_front();
+        }
+
+        samples.push_back(snapshot);
+    }
+}
+
+struct Sampler {
+    history: Arc<MetricsHistory>,
+    task: AbortHandle,
+}
+
+// The buffer being filled and the task filling it, if history is enabled.
+static SAMPLER: Lazy<Mutex<Option<Sampler>>> = Lazy::new(|| Mutex::new(None));
+
+async fn sample_periodically(history: Arc<MetricsHistory>, every: Duration) {
+    let mut interval: tokio::time::Interval = tokio::time::interval(every);
+
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        // Only fails if the runtime is gone, in which case this task is too.
+        if let Ok(snapshot) = MetricsSnapshot::capture() {
+            history.record(snapshot);
+        }
+    }
+}
+
+/// Forgets the sampler without touching its task, which belongs to a runtime a forked child
+/// doesn't have.
+pub fn reset() {
+    std::mem::forget(SAMPLER.lock().expect("Metrics sampler mutex was poisoned.").take());
+}
+
+/// Starts keeping the last `samples` readings of `fetch_metrics`, taken every `interval_ns`, for
+/// `metrics_history` to return. Enabling again restarts the history with the new settings, and
+/// passing `samples=0` disables it, stopping the sampler and dropping what it had collected.
+#[pyfunction]
+pub fn enable_metrics_history(samples: usize, interval_ns: u64) -> PyResult<()> {
+    let mut sampler = SAMPLER.lock().expect("Metrics sampler mutex was poisoned.");
+
+    if let Some(previous) = sampler.take() {
+        previous.task.abort();
+    }
+
+    if samples == 0 {
+        return Ok(());
+    }
+
+    if interval_ns == 0 {
+        return Err(PyErr::new::<PyValueError, _>("'interval_ns' must be positive."));
+    }
+
+    let history: Arc<MetricsHistory> = Arc::new(MetricsHistory { samples: Mutex::new(VecDeque::with_capacity(samples)), capacity: samples });
+    let task: AbortHandle = runtime::handle()?.spawn(sample_periodically(history.clone(), Duration::from_nanos(interval_ns))).abort_handle();
+
+    *sampler = Some(Sampler { history, task });
+
+    Ok(())
+}
+
+/// Returns the readings kept by `enable_metrics_history`, oldest first, as dicts shaped exactly
+/// like `fetch_metrics`'s, whose `sampled_at_ns` says when each was taken. Empty if history isn't
+/// enabled.
+#[pyfunction]
+pub fn metrics_history(py: Python<'_>) -> PyResult<Py<PyList>> {
+    let history: Option<Arc<MetricsHistory>> = SAMPLER
+        .lock()
+        .expect("Metrics sampler mutex was poisoned.")
+        .as_ref()
+        .map(|sampler| sampler.history.clone());
+
+    let py_list: Bound<'_, PyList> = PyList::empty(py);
+
+    if let Some(history) = history {
+        for snapshot in history.samples.lock().expect("Metrics history mutex was poisoned.").iter() {
+            py_list.append(snapshot.to_dict(py)?)?;
+        }
+    }
+
+    Ok(py_list.unbind())
+}
@@ -0,0 +1,46 @@
+use std::panic::PanicHookInfo;
+use std::sync::{Arc, Mutex};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+type PanicHook = Box<dyn Fn(&PanicHookInfo<'_>) + Sync + Send + 'static>;
+
+// The hook that was installed before switching to "abort", put back when switching to "catch".
+static PREVIOUS_HOOK: Mutex<Option<Arc<PanicHook>>> = Mutex::new(None);
+
+/// Chooses what a Rust panic inside coil does. With `"catch"` (the default) it only ends the
+/// task it happened in, whose handle then raises `TaskPanicked`. With `"abort"` the panic is
+/// reported as usual and then the whole process aborts, for deployments where a supervisor should
+/// restart it rather than keep running in a possibly broken state.
+#[pyfunction]
+pub fn set_panic_policy(policy: &str) -> PyResult<()> {
+    let mut previous = PREVIOUS_HOOK.lock().expect("Panic hook mutex was poisoned.");
+
+    match policy {
+        "abort" => {
+            if previous.is_none() {
+                let report: Arc<PanicHook> = Arc::new(std::panic::take_hook());
+
+                *previous = Some(report.clone());
+
+                std::panic::set_hook(Box::new(move |info: &PanicHookInfo<'_>| {
+                    report(info);
+                    std::process::abort();
+                }));
+            }
+        }
+        "catch" => {
+            if let Some(hook) = previous.take() {
+                std::panic::set_hook(Box::new(move |info: &PanicHookInfo<'_>| hook(info)));
+            }
+        }
+        _ => {
+            return Err(PyErr::new::<PyValueError, _>(format!(
+                "Unknown panic policy {policy:?}, expected \"abort\" or \"catch\"."
+            )))
+        }
+    }
+
+    Ok(())
+}
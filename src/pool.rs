@@ -0,0 +1,138 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyFunction};
+use tokio::sync::Notify;
+
+use crate::errors;
+use crate::gil;
+use crate::internal;
+use crate::queue::QueueInner;
+use crate::runtime;
+use crate::task::PyTaskHandle;
+
+struct PoolInner {
+    queue: QueueInner,
+    // Behind an `Arc` so workers can clone it for each item without taking the GIL.
+    handler: Arc<Py<PyFunction>>,
+    // Items submitted but not yet fully handled, whether still queued or being handled.
+    pending: AtomicUsize,
+    idle: Notify,
+}
+
+impl PoolInner {
+    async fn wait_idle(&self) {
+        loop {
+            let notified = self.idle.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if self.pending.load(Ordering::SeqCst) == 0 {
+                break
+            }
+
+            notified.await;
+        }
+    }
+}
+
+async fn run_worker(pool: Arc<PoolInner>) -> PyResult<PyObject> {
+    // Ends once the pool has been stopped and the queue has run dry.
+    while let Ok(item) = pool.queue.pop().await {
+        if let Err(error) = internal::exe_python_callable_async(pool.handler.clone(), Arc::new(item)).await {
+            errors::report_error_async(error, pool.handler.clone()).await;
+        }
+
+        if pool.pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+            pool.idle.notify_waiters();
+        }
+    }
+
+    gil::attach(|py| py.None())
+}
+
+/// Runs `num_workers` tasks that take items off a shared queue and call `handler(item)` on each.
+#[pyclass(name = "WorkerPool")]
+pub struct PyWorkerPool {
+    inner: Arc<PoolInner>,
+    workers: Mutex<Vec<PyTaskHandle>>,
+}
+
+#[pymethods]
+impl PyWorkerPool {
+    /// With `maxsize`, `submit` blocks while that many items are waiting for a worker. An
+    /// exception raised by `handler` is reported and the worker moves on to the next item.
+    #[new]
+    #[pyo3(signature = (num_workers, handler, *, maxsize = 0))]
+    fn new(py: Python<'_>, num_workers: usize, handler: Py<PyFunction>, maxsize: usize) -> PyResult<Self> {
+        if num_workers == 0 {
+            return Err(PyErr::new::<PyValueError, _>("'num_workers' must be positive."));
+        }
+
+        let name: String = format!("WorkerPool({})", internal::callable_name(handler.bind(py)));
+        let inner: Arc<PoolInner> = Arc::new(PoolInner {
+            queue: QueueInner::new(maxsize, 0, None),
+            handler: Arc::new(handler),
+            pending: AtomicUsize::new(0),
+            idle: Notify::new(),
+        });
+
+        let workers: Vec<PyTaskHandle> = (0..num_workers)
+            .map(|_| PyTaskHandle::spawn(name.clone(), run_worker(inner.clone())))
+            .collect::<PyResult<_>>()?;
+
+        Ok(Self { inner, workers: Mutex::new(workers) })
+    }
+
+    /// Queues `item` for the next free worker. Raises `QueueClosed` once the pool is stopped.
+    pub fn submit(&self, py: Python<'_>, item: Py<PyAny>) -> PyResult<()> {
+        let inner: Arc<PoolInner> = self.inner.clone();
+
+        self.inner.pending.fetch_add(1, Ordering::SeqCst);
+
+        let pushed: PyResult<()> = py.allow_threads(move || runtime::block_on(async move { inner.queue.push(item, 0).await }))?;
+
+        if pushed.is_err() && self.inner.pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.inner.idle.notify_waiters();
+        }
+
+        pushed
+    }
+
+    /// Blocks until every item submitted so far has been handled.
+    pub fn join(&self, py: Python<'_>) -> PyResult<()> {
+        let inner: Arc<PoolInner> = self.inner.clone();
+
+        py.allow_threads(move || runtime::block_on(async move { inner.wait_idle().await }))
+    }
+
+    /// Stops taking new items and blocks until the workers have handled what was already queued
+    /// and exited.
+    pub fn stop(&self, py: Python<'_>) -> PyResult<()> {
+        self.inner.queue.close(None);
+
+        let workers: Vec<PyTaskHandle> = std::mem::take(&mut *self.workers.lock().expect("Worker pool mutex was poisoned."));
+
+        for worker in workers {
+            worker.join(py, None)?;
+        }
+
+        Ok(())
+    }
+
+    /// Items submitted but not yet handled, including the ones being handled right now.
+    #[getter]
+    pub fn pending(&self) -> usize {
+        self.inner.pending.load(Ordering::SeqCst)
+    }
+}
+
+// A pool dropped without `stop` would otherwise leave its workers waiting on the queue forever.
+// Closing it lets them finish what was already queued and exit.
+impl Drop for PyWorkerPool {
+    fn drop(&mut self) {
+        self.inner.queue.close(None);
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use once_cell::sync::Lazy;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::runtime;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    High,
+    Normal,
+    Low,
+}
+
+impl Priority {
+    pub fn parse(priority: &str) -> PyResult<Self> {
+        match priority {
+            "high" => Ok(Self::High),
+            "normal" => Ok(Self::Normal),
+            "low" => Ok(Self::Low),
+            other => Err(PyErr::new::<PyValueError, _>(
+                format!("Unknown priority '{other}', expected 'high', 'normal' or 'low'.")
+            )),
+        }
+    }
+}
+
+// Tokio has no task priorities, so they are approximated by capping how much of the blocking pool
+// the lower classes may occupy: normal and low work together get at most three quarters of it,
+// low work alone at most a quarter. High priority work takes no permit at all, so however much
+// normal and low work piles up, at least a quarter of the pool is always left for it.
+struct PriorityPermits {
+    not_high: Arc<Semaphore>,
+    low: Arc<Semaphore>,
+}
+
+impl PriorityPermits {
+    fn new() -> Self {
+        let pool: usize = runtime::max_blocking_threads();
+
+        Self {
+            not_high: Arc::new(Semaphore::new((pool * 3 / 4).max(1))),
+            low: Arc::new(Semaphore::new((pool / 4).max(1))),
+        }
+    }
+}
+
+static PRIORITY_PERMITS: Lazy<Mutex<Arc<PriorityPermits>>> = Lazy::new(|| Mutex::new(Arc::new(PriorityPermits::new())));
+
+fn priority_permits() -> MutexGuard<'static, Arc<PriorityPermits>> {
+    PRIORITY_PERMITS.lock().expect("Priority permits mutex was poisoned.")
+}
+
+/// Starts over with semaphores at the configured size. Only for a forked child, where the
+/// callables holding permits from the old ones don't exist and would never give them back.
+pub fn reset() {
+    std::mem::forget(std::mem::replace(&mut *priority_permits(), Arc::new(PriorityPermits::new())));
+}
+
+/// Held for as long as the callable runs; dropping it hands the slots back.
+pub struct PriorityPermit {
+    _permits: Vec<OwnedSemaphorePermit>,
+}
+
+pub async fn acquire(priority: Priority) -> PriorityPermit {
+    let permits: Arc<PriorityPermits> = priority_permits().clone();
+
+    let semaphores: Vec<&Arc<Semaphore>> = match priority {
+        Priority::High => vec![],
+        Priority::Normal => vec![&permits.not_high],
+        Priority::Low => vec![&permits.low, &permits.not_high],
+    };
+
+    let mut acquired: Vec<OwnedSemaphorePermit> = Vec::with_capacity(semaphores.len());
+
+    for semaphore in semaphores {
+        acquired.push(semaphore.clone().acquire_owned().await.expect("Priority semaphores are never closed."));
+    }
+
+    PriorityPermit { _permits: acquired }
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use pyo3::exceptions::{PyStopIteration, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyAny;
+use tokio::sync::broadcast::{self, error::RecvError, error::TryRecvError};
+
+use crate::errors::QueueClosed;
+use crate::runtime;
+
+// Messages are shared between subscribers rather than copied, so every one of them gets the same
+// Python object.
+type Message = Arc<Py<PyAny>>;
+
+struct PubSubInner {
+    topics: Mutex<HashMap<String, broadcast::Sender<Message>>>,
+    capacity: usize,
+}
+
+impl PubSubInner {
+    fn topics(&self) -> MutexGuard<'_, HashMap<String, broadcast::Sender<Message>>> {
+        self.topics.lock().expect("PubSub topics mutex was poisoned.")
+    }
+}
+
+#[pyclass(name = "PubSub")]
+#[derive(Clone)]
+pub struct PyPubSub {
+    inner: Arc<PubSubInner>,
+}
+
+#[pymethods]
+impl PyPubSub {
+    /// `capacity` is how many messages per topic are kept for a subscriber that has fallen
+    /// behind; past that, the oldest are dropped for it and counted in its `missed`.
+    #[new]
+    #[pyo3(signature = (capacity = 1024))]
+    fn new(capacity: usize) -> PyResult<Self> {
+        if capacity == 0 {
+            return Err(PyErr::new::<PyValueError, _>("'capacity' must be positive."));
+        }
+
+        Ok(Self { inner: Arc::new(PubSubInner { topics: Mutex::new(HashMap::new()), capacity }) })
+    }
+
+    /// Sends `message` to every current subscriber of `topic`, returning how many there were.
+    /// Without subscribers the message is dropped.
+    pub fn publish(&self, topic: &str, message: Py<PyAny>) -> usize {
+        match self.inner.topics().get(topic) {
+            Some(sender) => sender.send(Arc::new(message)).unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    /// Starts receiving the messages published to `topic` from now on.
+    pub fn subscribe(&self, topic: String) -> PySubscription {
+        let receiver: broadcast::Receiver<Message> = self
+            .inner
+            .topics()
+            .entry(topic.clone())
+            .or_insert_with(|| broadcast::channel(self.inner.capacity).0)
+            .subscribe();
+
+        PySubscription {
+            topic,
+            receiver: Arc::new(tokio::sync::Mutex::new(receiver)),
+            missed: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// How many subscribers `topic` has.
+    pub fn subscribers(&self, topic: &str) -> usize {
+        self.inner.topics().get(topic).map_or(0, broadcast::Sender::receiver_count)
+    }
+
+    /// Ends every subscription, current ones seeing the end once they have read what was already
+    /// published. Subscribing or publishing again afterwards starts afresh.
+    pub fn close(&self) {
+        self.inner.topics().clear();
+    }
+}
+
+#[pyclass(name = "Subscription")]
+#[derive(Clone)]
+pub struct PySubscription {
+    topic: String,
+    receiver: Arc<tokio::sync::Mutex<broadcast::Receiver<Message>>>,
+    missed: Arc<AtomicU64>,
+}
+
+impl PySubscription {
+    fn closed_error(&self) -> PyErr {
+        PyErr::new::<QueueClosed, _>(format!("The PubSub hub for topic {:?} has been closed.", self.topic))
+    }
+}
+
+#[pymethods]
+impl PySubscription {
+    /// Blocks until the next message arrives. Raises `QueueClosed` once the hub is closed and
+    /// everything published before that has been read.
+    pub fn recv(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let receiver: Arc<tokio::sync::Mutex<broadcast::Receiver<Message>>> = self.receiver.clone();
+        let missed: Arc<AtomicU64> = self.missed.clone();
+
+        let message: Option<Message> = py.allow_threads(move || {
+            runtime::block_on(async move {
+                let mut receiver = receiver.lock().await;
+
+                loop {
+                    match receiver.recv().await {
+                        Ok(message) => break Some(message),
+                        Err(RecvError::Lagged(skipped)) => missed.fetch_add(skipped, Ordering::Relaxed),
+                        Err(RecvError::Closed) => break None,
+                    };
+                }
+            })
+        })?;
+
+        message.map(|message: Message| message.clone_ref(py)).ok_or_else(|| self.closed_error())
+    }
+
+    /// Returns the next message if one is waiting, and `None` otherwise.
+    pub fn try_recv(&self, py: Python<'_>) -> PyResult<Option<Py<PyAny>>> {
+        let Ok(mut receiver) = self.receiver.try_lock() else {
+            return Ok(None);
+        };
+
+        loop {
+            match receiver.try_recv() {
+                Ok(message) => return Ok(Some(message.clone_ref(py))),
+                Err(TryRecvError::Lagged(skipped)) => self.missed.fetch_add(skipped, Ordering::Relaxed),
+                Err(TryRecvError::Empty) => return Ok(None),
+                Err(TryRecvError::Closed) => return Err(self.closed_error()),
+            };
+        }
+    }
+
+    #[getter]
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// How many messages were dropped because this subscriber fell too far behind.
+    #[getter]
+    pub fn missed(&self) -> u64 {
+        self.missed.load(Ordering::Relaxed)
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        self.recv(py).map_err(|error: PyErr| {
+            if error.is_instance_of::<QueueClosed>(py) {
+                PyErr::new::<PyStopIteration, _>(())
+            } else {
+                error
+            }
+        })
+    }
+}
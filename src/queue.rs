@@ -0,0 +1,549 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use pyo3::exceptions::PyStopIteration;
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyIterator, PyList, PyModule};
+use tokio::sync::Notify;
+
+use crate::errors::{QueueClosed, TaskCancelled};
+use crate::exclusive;
+use crate::gil;
+use crate::hooks;
+use crate::runtime;
+use crate::task::{self, PyTaskHandle};
+
+struct PriorityEntry {
+    priority: i64,
+    sequence: u64,
+    item: Py<PyAny>,
+}
+
+impl PartialEq for PriorityEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PriorityEntry {}
+
+impl PartialOrd for PriorityEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PriorityEntry {
+    // `BinaryHeap` pops the greatest element, so the comparison is reversed: the lowest priority
+    // number wins, and among equal priorities the earliest insertion does.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.cmp(&self.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+struct PriorityHeap {
+    entries: BinaryHeap<PriorityEntry>,
+    next_sequence: u64,
+}
+
+struct PriorityQueueInner {
+    heap: Mutex<PriorityHeap>,
+    maxsize: usize,
+    not_empty: Notify,
+    not_full: Notify,
+}
+
+impl PriorityQueueInner {
+    fn heap(&self) -> MutexGuard<'_, PriorityHeap> {
+        self.heap.lock().expect("Priority queue mutex was poisoned.")
+    }
+
+    fn is_full(&self, heap: &PriorityHeap) -> bool {
+        self.maxsize != 0 && heap.entries.len() >= self.maxsize
+    }
+}
+
+#[pyclass(name = "PriorityQueue")]
+#[derive(Clone)]
+pub struct PyPriorityQueue {
+    inner: Arc<PriorityQueueInner>,
+}
+
+#[pymethods]
+impl PyPriorityQueue {
+    #[new]
+    #[pyo3(signature = (maxsize = 0))]
+    fn new(maxsize: usize) -> Self {
+        Self {
+            inner: Arc::new(PriorityQueueInner {
+                heap: Mutex::new(PriorityHeap { entries: BinaryHeap::new(), next_sequence: 0 }),
+                maxsize,
+                not_empty: Notify::new(),
+                not_full: Notify::new(),
+            }),
+        }
+    }
+
+    #[pyo3(signature = (item, priority = 0))]
+    pub fn put(&self, py: Python<'_>, item: Py<PyAny>, priority: i64) -> PyResult<()> {
+        let inner: Arc<PriorityQueueInner> = self.inner.clone();
+
+        py.allow_threads(move || {
+            runtime::block_on(async move {
+                loop {
+                    let notified = inner.not_full.notified();
+                    tokio::pin!(notified);
+                    notified.as_mut().enable();
+
+                    {
+                        let mut heap: MutexGuard<'_, PriorityHeap> = inner.heap();
+
+                        if !inner.is_full(&heap) {
+                            let sequence: u64 = heap.next_sequence;
+                            heap.next_sequence += 1;
+                            heap.entries.push(PriorityEntry { priority, sequence, item });
+
+                            break
+                        }
+                    }
+
+                    notified.await;
+                }
+
+                inner.not_empty.notify_one();
+            })
+        })
+    }
+
+    pub fn get(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let inner: Arc<PriorityQueueInner> = self.inner.clone();
+
+        py.allow_threads(move || {
+            runtime::block_on(async move {
+                let item: Py<PyAny> = loop {
+                    let notified = inner.not_empty.notified();
+                    tokio::pin!(notified);
+                    notified.as_mut().enable();
+
+                    if let Some(entry) = inner.heap().entries.pop() {
+                        break entry.item
+                    }
+
+                    notified.await;
+                };
+
+                inner.not_full.notify_one();
+
+                item
+            })
+        })
+    }
+
+    pub fn qsize(&self) -> usize {
+        self.inner.heap().entries.len()
+    }
+
+    pub fn empty(&self) -> bool {
+        self.inner.heap().entries.is_empty()
+    }
+
+    pub fn full(&self) -> bool {
+        let heap: MutexGuard<'_, PriorityHeap> = self.inner.heap();
+
+        self.inner.is_full(&heap)
+    }
+
+    fn __len__(&self) -> usize {
+        self.qsize()
+    }
+}
+
+struct QueueState {
+    // Each item with the size it was counted as when it went in.
+    items: VecDeque<(Py<PyAny>, usize)>,
+    bytes: usize,
+    closed: bool,
+    // What `get` raises once the queue is closed and empty; `QueueClosed` if unset.
+    // Behind an `Arc` so it can be copied out and the lock let go before the GIL is taken.
+    error: Option<Arc<PyErr>>,
+}
+
+pub struct QueueInner {
+    state: Mutex<QueueState>,
+    maxsize: usize,
+    max_bytes: usize,
+    // Measures items against `max_bytes`; `sys.getsizeof` if unset.
+    sizer: Option<Py<PyAny>>,
+    not_empty: Notify,
+    not_full: Notify,
+}
+
+impl QueueInner {
+    pub fn new(maxsize: usize, max_bytes: usize, sizer: Option<Py<PyAny>>) -> Self {
+        Self {
+            state: Mutex::new(QueueState { items: VecDeque::new(), bytes: 0, closed: false, error: None }),
+            maxsize,
+            max_bytes,
+            sizer,
+            not_empty: Notify::new(),
+            not_full: Notify::new(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().expect("Queue mutex was poisoned.")
+    }
+
+    fn is_full(&self, state: &QueueState) -> bool {
+        self.maxsize != 0 && state.items.len() >= self.maxsize
+    }
+
+    // An item bigger than the whole budget is still let into an empty queue, or it could never go in.
+    fn has_room_for(&self, state: &QueueState, size: usize) -> bool {
+        !self.is_full(state)
+            && (self.max_bytes == 0 || state.items.is_empty() || state.bytes.saturating_add(size) <= self.max_bytes)
+    }
+
+    /// The size `item` counts as against `max_bytes`, which is 0 when there is no byte limit.
+    pub fn measure(&self, py: Python<'_>, item: &Py<PyAny>) -> PyResult<usize> {
+        if self.max_bytes == 0 {
+            return Ok(0);
+        }
+
+        match &self.sizer {
+            Some(sizer) => sizer.call1(py, (item,))?.extract(py),
+            None => PyModule::import(py, "sys")?.getattr("getsizeof")?.call1((item,))?.extract(),
+        }
+    }
+
+    fn closed_error() -> PyErr {
+        PyErr::new::<QueueClosed, _>("The queue has been closed.")
+    }
+
+    // Called with the state lock already let go: everything else that takes it from Python holds
+    // the GIL first, so taking the GIL under it could deadlock.
+    fn closed_with(error: Option<Arc<PyErr>>) -> PyResult<PyErr> {
+        match error {
+            Some(error) => gil::attach(|py| error.clone_ref(py)),
+            None => Ok(Self::closed_error()),
+        }
+    }
+
+    /// Waits for room and appends `item`, which is `size` bytes as given by `measure`, failing if
+    /// the queue is (or gets) closed first.
+    pub async fn push(&self, item: Py<PyAny>, size: usize) -> PyResult<()> {
+        loop {
+            let notified = self.not_full.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            {
+                let mut state: MutexGuard<'_, QueueState> = self.state();
+
+                if state.closed {
+                    return Err(Self::closed_error());
+                }
+
+                if self.has_room_for(&state, size) {
+                    state.items.push_back((item, size));
+                    state.bytes += size;
+                    break
+                }
+            }
+
+            notified.await;
+        }
+
+        self.not_empty.notify_one();
+        Ok(())
+    }
+
+    /// Waits for an item. Once the queue is closed, the buffered items are still handed out, and
+    /// after that this returns the error it was closed with.
+    pub async fn pop(&self) -> PyResult<Py<PyAny>> {
+        let item: Py<PyAny> = loop {
+            let notified = self.not_empty.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            {
+                let mut state: MutexGuard<'_, QueueState> = self.state();
+
+                if let Some((item, size)) = state.items.pop_front() {
+                    state.bytes -= size;
+                    break item
+                }
+
+                if state.closed {
+                    let error: Option<Arc<PyErr>> = state.error.clone();
+                    drop(state);
+
+                    return Err(Self::closed_with(error)?);
+                }
+            }
+
+            notified.await;
+        };
+
+        self.freed_room();
+        Ok(item)
+    }
+
+    /// Takes an item if one is there, without waiting. `Ok(None)` means the queue is empty but
+    /// still open; once it is closed and empty this returns the error it was closed with.
+    pub fn try_pop(&self) -> PyResult<Option<Py<PyAny>>> {
+        let item: Py<PyAny> = {
+            let mut state: MutexGuard<'_, QueueState> = self.state();
+
+            match state.items.pop_front() {
+                Some((item, size)) => {
+                    state.bytes -= size;
+                    item
+                }
+                None if state.closed => {
+                    let error: Option<Arc<PyErr>> = state.error.clone();
+                    drop(state);
+
+                    return Err(Self::closed_with(error)?);
+                }
+                None => return Ok(None),
+            }
+        };
+
+        self.freed_room();
+        Ok(Some(item))
+    }
+
+    fn freed_room(&self) {
+        // With a byte budget, what was freed may fit several smaller waiting items, or not the
+        // next one in line, so everyone gets to re-check.
+        if self.max_bytes == 0 {
+            self.not_full.notify_one();
+        } else {
+            self.not_full.notify_waiters();
+        }
+    }
+
+    /// Stops the queue accepting items and wakes everyone waiting on it. Only the first close
+    /// counts; later ones (and their errors) are ignored.
+    pub fn close(&self, error: Option<PyErr>) {
+        {
+            let mut state: MutexGuard<'_, QueueState> = self.state();
+
+            if state.closed {
+                return
+            }
+
+            state.closed = true;
+            state.error = error.map(Arc::new);
+        }
+
+        self.not_empty.notify_waiters();
+        self.not_full.notify_waiters();
+    }
+}
+
+/// A FIFO queue for passing objects between tasks, which can be closed once the producer is done.
+#[pyclass(name = "Queue")]
+#[derive(Clone)]
+pub struct PyQueue {
+    inner: Arc<QueueInner>,
+}
+
+#[pymethods]
+impl PyQueue {
+    /// `maxsize` caps the number of items and `max_bytes` their total size as measured by
+    /// `sizer(item)` (`sys.getsizeof` by default); `put` blocks while either is reached. 0 means
+    /// no limit.
+    #[new]
+    #[pyo3(signature = (maxsize = 0, *, max_bytes = 0, sizer = None))]
+    fn new(maxsize: usize, max_bytes: usize, sizer: Option<Py<PyAny>>) -> Self {
+        Self { inner: Arc::new(QueueInner::new(maxsize, max_bytes, sizer)) }
+    }
+
+    /// Blocks while the queue is full. Raises `QueueClosed` if it has been closed.
+    pub fn put(&self, py: Python<'_>, item: Py<PyAny>) -> PyResult<()> {
+        let inner: Arc<QueueInner> = self.inner.clone();
+        let size: usize = inner.measure(py, &item)?;
+
+        py.allow_threads(move || runtime::block_on(async move { inner.push(item, size).await }))?
+    }
+
+    /// Blocks until an item is available. Once the queue is closed and empty, raises `QueueClosed`,
+    /// or the exception the queue was closed with.
+    pub fn get(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let inner: Arc<QueueInner> = self.inner.clone();
+
+        py.allow_threads(move || runtime::block_on(async move { inner.pop().await }))?
+    }
+
+    /// Closes the queue. Items already in it can still be taken; after that, `get` raises `error`
+    /// if one is given and `QueueClosed` otherwise.
+    #[pyo3(signature = (error = None))]
+    pub fn close(&self, error: Option<Bound<'_, PyAny>>) {
+        self.inner.close(error.map(PyErr::from_value));
+    }
+
+    /// Takes everything currently in the queue, without waiting for more. Never raises, even
+    /// once the queue is closed; a drained queue just gives an empty list.
+    pub fn drain(&self, py: Python<'_>) -> PyResult<Py<PyList>> {
+        let items: Vec<Py<PyAny>> = {
+            let mut state: MutexGuard<'_, QueueState> = self.inner.state();
+
+            state.bytes = 0;
+            state.items.drain(..).map(|(item, _)| item).collect()
+        };
+
+        if !items.is_empty() {
+            self.inner.not_full.notify_waiters();
+        }
+
+        Ok(PyList::new(py, items)?.unbind())
+    }
+
+    /// Returns an item if one is waiting, and `None` otherwise. Like `get`, raises once the queue
+    /// is closed and empty.
+    pub fn try_get(&self) -> PyResult<Option<Py<PyAny>>> {
+        self.inner.try_pop()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.state().closed
+    }
+
+    pub fn qsize(&self) -> usize {
+        self.inner.state().items.len()
+    }
+
+    pub fn empty(&self) -> bool {
+        self.inner.state().items.is_empty()
+    }
+
+    /// The total size of the items in the queue, as counted against `max_bytes`.
+    pub fn qbytes(&self) -> usize {
+        self.inner.state().bytes
+    }
+
+    pub fn full(&self) -> bool {
+        let state: MutexGuard<'_, QueueState> = self.inner.state();
+
+        self.inner.is_full(&state) || (self.inner.max_bytes != 0 && state.bytes >= self.inner.max_bytes)
+    }
+
+    fn __len__(&self) -> usize {
+        self.qsize()
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Iterating ends when the queue is closed normally; an error it was closed with is raised.
+    fn __next__(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        self.get(py).map_err(|error: PyErr| {
+            if error.is_instance_of::<QueueClosed>(py) {
+                PyErr::new::<PyStopIteration, _>(())
+            } else {
+                error
+            }
+        })
+    }
+}
+
+/// Drives the iterator (usually a generator) `generator` on the blocking pool, putting every item
+/// it yields into `queue` and closing `queue` when it is exhausted. If it raises, or anything else
+/// stops it, the queue is closed with that exception, which consumers see once they have taken the
+/// earlier items. The returned handle finishes with `None`, or raises the same exception.
+#[pyfunction]
+pub fn spawn_generator(generator: Bound<'_, PyAny>, queue: PyQueue) -> PyResult<PyTaskHandle> {
+    let name: String = format!("spawn_generator({})", generator.get_type().name()?);
+    let iterator: Py<PyIterator> = PyIterator::from_object(&generator)?.unbind();
+    let inner: Arc<QueueInner> = queue.inner;
+
+    PyTaskHandle::spawn(name, async move {
+        let producer: ProducerGuard = ProducerGuard(inner.clone());
+        let fed: PyResult<()> = feed(Arc::new(iterator), inner).await;
+
+        match fed {
+            Ok(()) => {
+                producer.0.close(None);
+                gil::attach(|py_done| py_done.None())
+            }
+            Err(error) => {
+                let reported: PyResult<PyErr> = gil::attach(|py_error| error.clone_ref(py_error));
+
+                producer.0.close(Some(error));
+                Err(reported?)
+            }
+        }
+    })
+}
+
+// Closes the queue `spawn_generator` feeds if its task is dropped before it could, e.g. when it is
+// cancelled, so consumers aren't left waiting on items that will never come.
+struct ProducerGuard(Arc<QueueInner>);
+
+impl Drop for ProducerGuard {
+    fn drop(&mut self) {
+        self.0.close(Some(PyErr::new::<TaskCancelled, _>("The generator feeding the queue was cancelled.")));
+    }
+}
+
+async fn feed(iterator: Arc<Py<PyIterator>>, inner: Arc<QueueInner>) -> PyResult<()> {
+    loop {
+        let source: Arc<Py<PyIterator>> = iterator.clone();
+        let queue: Arc<QueueInner> = inner.clone();
+        let shared: exclusive::SharedGuard = exclusive::shared().await;
+        let next: Option<PyResult<(Py<PyAny>, usize)>> = tokio::task::spawn_blocking(move || {
+            gil::attach(|py_blocking| {
+                hooks::prepare_thread(py_blocking);
+
+                source.bind(py_blocking).clone().next().map(|item| {
+                    let item: Py<PyAny> = item?.unbind();
+                    let size: usize = queue.measure(py_blocking, &item)?;
+
+                    Ok((item, size))
+                })
+            })
+        })
+        .await
+        .map_err(task::join_error)??;
+
+        drop(shared);
+
+        match next {
+            Some(Ok((item, size))) => {
+                // A consumer closed the queue, so nobody wants the rest.
+                if inner.push(item, size).await.is_err() {
+                    return Ok(());
+                }
+            }
+            Some(Err(error)) => return Err(error),
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Takes an item from the first of `queues` that has one waiting, returning `(index, item)`, or
+/// `None` straight away if none of them do. Closed queues are skipped; if every queue is closed
+/// and empty, raises what the last of them was closed with.
+#[pyfunction]
+pub fn try_select_queues(queues: Vec<PyQueue>) -> PyResult<Option<(usize, Py<PyAny>)>> {
+    let mut any_open: bool = queues.is_empty();
+    let mut closed: Option<PyErr> = None;
+
+    for (index, queue) in queues.iter().enumerate() {
+        match queue.inner.try_pop() {
+            Ok(Some(item)) => return Ok(Some((index, item))),
+            Ok(None) => any_open = true,
+            Err(error) => closed = Some(error),
+        }
+    }
+
+    match closed {
+        Some(error) if !any_open => Err(error),
+        _ => Ok(None),
+    }
+}
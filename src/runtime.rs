@@ -0,0 +1,550 @@
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyFunction, PyTuple};
+use tokio::runtime::{Builder, EnterGuard, Handle, Runtime};
+use tokio::task::AbortHandle;
+
+use crate::errors;
+use crate::exclusive;
+use crate::group;
+use crate::limit;
+use crate::log;
+use crate::metrics;
+use crate::priority;
+use crate::task;
+
+#[derive(Default)]
+struct RuntimeConfig {
+    built: bool,
+    shut_down: bool,
+    event_interval: Option<u32>,
+    global_queue_interval: Option<u32>,
+    idle_shutdown: Option<Duration>,
+    thread_stack_size: Option<usize>,
+    worker_threads: Option<usize>,
+    max_blocking_threads: Option<usize>,
+    thread_name: Option<String>,
+}
+
+// Tokio's own default for the blocking pool.
+const DEFAULT_MAX_BLOCKING_THREADS: usize = 512;
+
+// Python frames are large, and anything below this overflows on the first few nested calls.
+const MIN_THREAD_STACK_SIZE: usize = 256 * 1024;
+
+// How long interpreter exit waits for callables that are still running before leaving them be.
+const EXIT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+
+static RUNTIME_CONFIG: Lazy<Mutex<RuntimeConfig>> = Lazy::new(|| Mutex::new(RuntimeConfig::default()));
+
+// The runtime sits behind an `Option` so the idle watchdog can take it down; the next call to
+// `handle` builds a fresh one with the same config.
+static TOKIO_RUNTIME: RwLock<Option<Runtime>> = RwLock::new(None);
+static RUNTIME_GENERATION: AtomicU64 = AtomicU64::new(0);
+// The process the runtime was built in. Its threads don't survive a fork, so a child seeing
+// someone else's pid here has to start over.
+static RUNTIME_PID: AtomicU32 = AtomicU32::new(0);
+
+static SHUTDOWN_HOOKS: Lazy<Mutex<Vec<Py<PyFunction>>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+static EPOCH: Lazy<Instant> = Lazy::new(Instant::now);
+static LAST_ACTIVITY_NS: AtomicU64 = AtomicU64::new(0);
+static STARTED_AT_NS: AtomicU64 = AtomicU64::new(0);
+static ACTIVE_BLOCKERS: AtomicUsize = AtomicUsize::new(0);
+// Housekeeping tasks, like cache sweepers, which don't count against the runtime being idle.
+static BACKGROUND_TASKS: AtomicUsize = AtomicUsize::new(0);
+
+/// Nanoseconds on a monotonic clock shared by everything in coil that keeps deadlines.
+pub fn now_ns() -> u64 {
+    EPOCH.elapsed().as_nanos() as u64
+}
+
+/// The instant `now_ns` reads as `ns`, for timers that wait until an absolute deadline.
+pub fn instant_at(ns: u64) -> tokio::time::Instant {
+    tokio::time::Instant::from_std(*EPOCH + Duration::from_nanos(ns))
+}
+
+/// Nanoseconds on coil's monotonic clock, the one absolute deadlines such as
+/// `MutexLock.try_acquire_until`'s are measured on. Only differences between readings mean
+/// anything.
+#[pyfunction]
+pub fn monotonic_ns() -> u64 {
+    now_ns()
+}
+
+fn build_runtime() -> PyResult<Runtime> {
+    let mut config: MutexGuard<'_, RuntimeConfig> = runtime_config();
+
+    if config.shut_down {
+        return Err(PyErr::new::<PyRuntimeError, _>("The runtime has been shut down."));
+    }
+
+    let mut builder: Builder = Builder::new_multi_thread();
+
+    builder.enable_all();
+
+    if let Some(event_interval) = config.event_interval {
+        builder.event_interval(event_interval);
+    }
+    if let Some(global_queue_interval) = config.global_queue_interval {
+        builder.global_queue_interval(global_queue_interval);
+    }
+    if let Some(thread_stack_size) = config.thread_stack_size {
+        builder.thread_stack_size(thread_stack_size);
+    }
+    if let Some(worker_threads) = config.worker_threads {
+        builder.worker_threads(worker_threads);
+    }
+    if let Some(max_blocking_threads) = config.max_blocking_threads {
+        builder.max_blocking_threads(max_blocking_threads);
+    }
+    if let Some(thread_name) = &config.thread_name {
+        builder.thread_name(thread_name);
+    }
+
+    let runtime: Runtime = builder.build().map_err(|e: std::io::Error| {
+        PyErr::new::<PyRuntimeError, _>(format!("Failed to create tokio runtime: {}", e))
+    })?;
+
+    let generation: u64 = RUNTIME_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    STARTED_AT_NS.store(now_ns(), Ordering::SeqCst);
+    RUNTIME_PID.store(std::process::id(), Ordering::SeqCst);
+
+    if let Some(idle) = config.idle_shutdown {
+        spawn_idle_watchdog(idle, generation)?;
+    }
+
+    config.built = true;
+
+    Ok(runtime)
+}
+
+fn runtime_slot() -> RwLockReadGuard<'static, Option<Runtime>> {
+    TOKIO_RUNTIME.read().expect("Runtime lock was poisoned.")
+}
+
+fn runtime_slot_mut() -> RwLockWriteGuard<'static, Option<Runtime>> {
+    TOKIO_RUNTIME.write().expect("Runtime lock was poisoned.")
+}
+
+fn is_idle(runtime: &Runtime, idle: Duration) -> bool {
+    let quiet_for: u64 = now_ns().saturating_sub(LAST_ACTIVITY_NS.load(Ordering::SeqCst));
+
+    quiet_for >= idle.as_nanos() as u64
+        && ACTIVE_BLOCKERS.load(Ordering::SeqCst) == 0
+        && runtime.metrics().num_alive_tasks() <= BACKGROUND_TASKS.load(Ordering::SeqCst)
+}
+
+// Runs on a plain OS thread rather than as a task, since it has to outlive the runtime it drops.
+fn spawn_idle_watchdog(idle: Duration, generation: u64) -> PyResult<()> {
+    let check_every: Duration = (idle / 4).clamp(Duration::from_millis(1), Duration::from_secs(1));
+
+    std::thread::Builder::new()
+        .name("coil-idle-watchdog".to_string())
+        .spawn(move || loop {
+            std::thread::sleep(check_every);
+
+            let idle_now: bool = match runtime_slot().as_ref() {
+                Some(runtime) if RUNTIME_GENERATION.load(Ordering::SeqCst) == generation => is_idle(runtime, idle),
+                _ => return,
+            };
+
+            if !idle_now {
+                continue
+            }
+
+            // The check above was only a first look. This one decides, and `handle` can't record
+            // activity while the write lock is held.
+            let mut slot: RwLockWriteGuard<'_, Option<Runtime>> = runtime_slot_mut();
+
+            if RUNTIME_GENERATION.load(Ordering::SeqCst) != generation {
+                return
+            }
+
+            if let Some(runtime) = slot.take_if(|runtime: &mut Runtime| is_idle(runtime, idle)) {
+                runtime.shutdown_background();
+                return
+            }
+        })
+        .map(drop)
+        .map_err(|e: std::io::Error| PyErr::new::<PyRuntimeError, _>(format!("Failed to start the idle watchdog: {}", e)))
+}
+
+// Dropping a runtime joins its threads, and in a forked child those were never there, so the
+// inherited one is leaked instead, along with everything else that points into it. Does nothing
+// unless the runtime was built by another process, so it can't leak a live one here, however
+// often it is called. `slot` is the write-locked runtime slot, which also keeps two threads from
+// doing this at once.
+fn discard_inherited_state(slot: &mut Option<Runtime>) {
+    let pid: u32 = RUNTIME_PID.load(Ordering::SeqCst);
+
+    if pid == 0 || pid == std::process::id() {
+        return
+    }
+
+    std::mem::forget(slot.take());
+    RUNTIME_GENERATION.fetch_add(1, Ordering::SeqCst);
+    ACTIVE_BLOCKERS.store(0, Ordering::SeqCst);
+    BACKGROUND_TASKS.store(0, Ordering::SeqCst);
+    RUNTIME_PID.store(std::process::id(), Ordering::SeqCst);
+
+    exclusive::reset();
+    group::reset();
+    limit::reset();
+    log::reset();
+    metrics::reset();
+    priority::reset();
+    task::reset();
+}
+
+/// Returns a handle to the shared runtime, building it on first use (or after an idle shutdown).
+/// Fails once `shutdown` has been called.
+pub fn handle() -> PyResult<Handle> {
+    let pid: u32 = RUNTIME_PID.load(Ordering::SeqCst);
+
+    // A fork that skipped `os.register_at_fork`'s hooks, e.g. one made from C. Unlike the hooks,
+    // this can't rule out the lock having been held at the time of the fork by a thread that
+    // doesn't exist here, but every holder lets go of it quickly, so that is unlikely.
+    if pid != 0 && pid != std::process::id() {
+        discard_inherited_state(&mut runtime_slot_mut());
+    }
+
+    // The activity is recorded while holding the lock, and the idle watchdog only takes the
+    // runtime down after re-checking it under the write lock, so it either sees this call or has
+    // already finished, in which case the slot is empty here and a new runtime gets built.
+    if let Some(runtime) = runtime_slot().as_ref() {
+        LAST_ACTIVITY_NS.store(now_ns(), Ordering::SeqCst);
+        return Ok(runtime.handle().clone());
+    }
+
+    let mut slot: RwLockWriteGuard<'_, Option<Runtime>> = runtime_slot_mut();
+
+    LAST_ACTIVITY_NS.store(now_ns(), Ordering::SeqCst);
+
+    if slot.is_none() {
+        *slot = Some(build_runtime()?);
+    }
+
+    Ok(slot.as_ref().expect("Runtime was just built.").handle().clone())
+}
+
+struct BlockerGuard;
+
+impl Drop for BlockerGuard {
+    fn drop(&mut self) {
+        ACTIVE_BLOCKERS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Drives `future` to completion on the calling thread. Callers blocked here count as activity,
+/// so the idle watchdog never pulls the runtime out from under a pending wait.
+pub fn block_on<F: Future>(future: F) -> PyResult<F::Output> {
+    ACTIVE_BLOCKERS.fetch_add(1, Ordering::SeqCst);
+    let _guard: BlockerGuard = BlockerGuard;
+
+    Ok(handle()?.block_on(future))
+}
+
+struct BackgroundGuard;
+
+impl Drop for BackgroundGuard {
+    fn drop(&mut self) {
+        BACKGROUND_TASKS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Spawns a housekeeping task that doesn't keep the idle watchdog from shutting the runtime down,
+/// since it has nothing to do with what the user is running. It goes down with the runtime.
+pub fn spawn_background<F>(future: F) -> PyResult<AbortHandle>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let runtime: Handle = handle()?;
+
+    BACKGROUND_TASKS.fetch_add(1, Ordering::SeqCst);
+    // Moved into the task rather than created in it, so it is dropped even if the task never runs.
+    let guard: BackgroundGuard = BackgroundGuard;
+
+    Ok(runtime
+        .spawn(async move {
+            let _guard: BackgroundGuard = guard;
+            future.await
+        })
+        .abort_handle())
+}
+
+/// How long the current runtime has been up.
+pub fn uptime() -> Duration {
+    Duration::from_nanos(now_ns().saturating_sub(STARTED_AT_NS.load(Ordering::SeqCst)))
+}
+
+fn runtime_config() -> MutexGuard<'static, RuntimeConfig> {
+    RUNTIME_CONFIG.lock().expect("Runtime config mutex was poisoned.")
+}
+
+/// The size of the runtime's blocking pool, which is what bounds how many callables run at once.
+pub fn max_blocking_threads() -> usize {
+    runtime_config().max_blocking_threads.unwrap_or(DEFAULT_MAX_BLOCKING_THREADS)
+}
+
+fn positive<T: TryFrom<i64>>(name: &str, value: i64) -> PyResult<T> {
+    if value <= 0 {
+        return Err(PyErr::new::<PyValueError, _>(format!("'{name}' must be a positive integer, got {value}.")));
+    }
+
+    T::try_from(value).map_err(|_| PyErr::new::<PyValueError, _>(format!("'{name}' is too large, got {value}.")))
+}
+
+/// Tunes the runtime before it is started. Only the options that are passed are changed;
+/// calling this once the runtime exists (i.e. after the first spawn) is an error.
+///
+/// With `idle_shutdown_ns`, the runtime and its worker threads are torn down once nothing has
+/// touched it for that long and no tasks are alive; it is rebuilt transparently on next use.
+/// `thread_stack_size` (in bytes) and `thread_name` apply to both worker and blocking threads.
+/// `worker_threads` defaults to one per core. `max_blocking_threads` sizes the pool callables
+/// run on, and so how many of them run at once; it defaults to 512.
+#[pyfunction]
+#[pyo3(signature = (
+    *,
+    event_interval = None,
+    global_queue_interval = None,
+    idle_shutdown_ns = None,
+    thread_stack_size = None,
+    worker_threads = None,
+    max_blocking_threads = None,
+    thread_name = None,
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn configure_runtime(
+    event_interval: Option<i64>,
+    global_queue_interval: Option<i64>,
+    idle_shutdown_ns: Option<i64>,
+    thread_stack_size: Option<i64>,
+    worker_threads: Option<i64>,
+    max_blocking_threads: Option<i64>,
+    thread_name: Option<String>,
+) -> PyResult<()> {
+    let event_interval: Option<u32> = event_interval.map(|v| positive("event_interval", v)).transpose()?;
+    let global_queue_interval: Option<u32> = global_queue_interval
+        .map(|v| positive("global_queue_interval", v))
+        .transpose()?;
+    let idle_shutdown: Option<Duration> = idle_shutdown_ns
+        .map(|v| positive("idle_shutdown_ns", v).map(Duration::from_nanos))
+        .transpose()?;
+    let thread_stack_size: Option<usize> = thread_stack_size
+        .map(|v| match usize::try_from(v) {
+            Ok(v) if v >= MIN_THREAD_STACK_SIZE => Ok(v),
+            _ => Err(PyErr::new::<PyValueError, _>(format!(
+                "'thread_stack_size' must be at least {MIN_THREAD_STACK_SIZE} bytes, got {v}."
+            ))),
+        })
+        .transpose()?;
+    let worker_threads: Option<usize> = worker_threads.map(|v| positive("worker_threads", v)).transpose()?;
+    let max_blocking_threads: Option<usize> = max_blocking_threads
+        .map(|v| positive("max_blocking_threads", v))
+        .transpose()?;
+
+    if thread_name.as_deref().is_some_and(str::is_empty) {
+        return Err(PyErr::new::<PyValueError, _>("'thread_name' must not be empty."));
+    }
+
+    let mut config: MutexGuard<'_, RuntimeConfig> = runtime_config();
+
+    if config.built {
+        return Err(PyErr::new::<PyRuntimeError, _>(
+            "The runtime has already been started, configure_runtime must be called before the first spawn."
+        ));
+    }
+
+    if event_interval.is_some() {
+        config.event_interval = event_interval;
+    }
+    if global_queue_interval.is_some() {
+        config.global_queue_interval = global_queue_interval;
+    }
+    if idle_shutdown.is_some() {
+        config.idle_shutdown = idle_shutdown;
+    }
+    if thread_stack_size.is_some() {
+        config.thread_stack_size = thread_stack_size;
+    }
+    if worker_threads.is_some() {
+        config.worker_threads = worker_threads;
+    }
+    if max_blocking_threads.is_some() {
+        config.max_blocking_threads = max_blocking_threads;
+    }
+    if thread_name.is_some() {
+        config.thread_name = thread_name;
+    }
+
+    Ok(())
+}
+
+/// Starts the runtime now instead of on the first spawn, so thread creation happens at a
+/// predictable point and a failure to build it surfaces here as a `RuntimeError`. Calling it
+/// again is a no-op.
+#[pyfunction]
+pub fn init() -> PyResult<()> {
+    handle().map(drop)
+}
+
+/// Blocks, with the GIL released, until at most `baseline` tasks are alive on the runtime,
+/// checking every `poll_interval_ns`. That includes fire-and-forget `new_thread` tasks whose
+/// handles were dropped, so this is how to be sure they have all drained. Long-lived background
+/// tasks (a `TtlCache`'s sweeper, an armed `ResettableTimer`, metrics history) count too; pass
+/// how many of those to expect as `baseline`. Signals are checked between polls.
+#[pyfunction]
+#[pyo3(signature = (poll_interval_ns = 1_000_000, baseline = 0))]
+pub fn wait_until_idle(py: Python<'_>, poll_interval_ns: u64, baseline: usize) -> PyResult<()> {
+    let poll_interval: Duration = Duration::from_nanos(poll_interval_ns.max(1));
+
+    while handle()?.metrics().num_alive_tasks() > baseline {
+        py.allow_threads(move || block_on(async move { tokio::time::sleep(poll_interval).await }))?;
+        py.check_signals()?;
+    }
+
+    Ok(())
+}
+
+/// Registers `py_func` to be called, with no arguments, when `shutdown` runs. Hooks run in the
+/// reverse order they were registered, while the runtime is still up; an exception from one is
+/// reported and does not stop the others or the shutdown.
+#[pyfunction]
+pub fn register_shutdown_hook(py_func: Py<PyFunction>) {
+    SHUTDOWN_HOOKS.lock().expect("Shutdown hooks mutex was poisoned.").push(py_func);
+}
+
+/// Runs the shutdown hooks, then stops the runtime, waiting for callables that have already
+/// started to return; tasks that haven't started are dropped. With `timeout_ns`, gives up waiting
+/// after that long and leaves whatever is still running to finish on its own. Anything that
+/// needs the runtime raises `RuntimeError` afterwards.
+#[pyfunction]
+#[pyo3(signature = (timeout_ns = None))]
+pub fn shutdown(py: Python<'_>, timeout_ns: Option<u64>) -> PyResult<()> {
+    loop {
+        // Popped one at a time so a hook can itself register hooks, which then run next.
+        let hook: Option<Py<PyFunction>> = SHUTDOWN_HOOKS.lock().expect("Shutdown hooks mutex was poisoned.").pop();
+
+        let Some(hook) = hook else { break };
+
+        if let Err(error) = hook.call0(py) {
+            errors::report_error(py, error, hook.bind(py).as_any());
+        }
+    }
+
+    runtime_config().shut_down = true;
+    RUNTIME_GENERATION.fetch_add(1, Ordering::SeqCst);
+
+    let runtime: Option<Runtime> = runtime_slot_mut().take();
+
+    if let Some(runtime) = runtime {
+        py.allow_threads(move || match timeout_ns {
+            Some(timeout_ns) => runtime.shutdown_timeout(Duration::from_nanos(timeout_ns)),
+            None => drop(runtime),
+        });
+    }
+
+    Ok(())
+}
+
+/// Registered with `atexit`, so the runtime is stopped while the interpreter can still run the
+/// callables on it. Those still running after `EXIT_SHUTDOWN_TIMEOUT` are abandoned to the
+/// interpreter's own shutdown, as daemon threads are.
+#[pyfunction]
+pub fn shutdown_at_exit(py: Python<'_>) -> PyResult<()> {
+    shutdown(py, Some(EXIT_SHUTDOWN_TIMEOUT.as_nanos() as u64))
+}
+
+thread_local! {
+    // The runtime slot's write lock, held by the forking thread from just before a fork until
+    // just after it, in both processes.
+    static FORK_GUARD: RefCell<Option<RwLockWriteGuard<'static, Option<Runtime>>>> = const { RefCell::new(None) };
+}
+
+/// Registered with `os.register_at_fork` to run just before a fork. Holding the runtime lock
+/// across the fork means no other thread can be holding it when the child is made, since in the
+/// child that thread would be gone and the lock never freed. Nothing that takes the lock waits on
+/// the GIL while holding it, so waiting for it here with the GIL held can't deadlock.
+#[pyfunction]
+pub fn before_fork() {
+    let guard: RwLockWriteGuard<'static, Option<Runtime>> = runtime_slot_mut();
+
+    FORK_GUARD.with(|held: &RefCell<Option<RwLockWriteGuard<'static, Option<Runtime>>>>| *held.borrow_mut() = Some(guard));
+}
+
+/// Registered to run in the parent after a fork, letting go of the lock `before_fork` took.
+#[pyfunction]
+pub fn after_fork_in_parent() {
+    FORK_GUARD.with(|held: &RefCell<Option<RwLockWriteGuard<'static, Option<Runtime>>>>| held.borrow_mut().take());
+}
+
+/// Throws away the runtime and everything coil was tracking, so the next use starts a fresh one.
+/// It only does anything in the child of a `fork`, which inherits the parent's runtime but none
+/// of its threads; in the process that built the runtime it is a no-op. Coil registers it with
+/// `os.register_at_fork` and also does this by itself on first use in a new process, so calling
+/// it by hand is rarely needed. Handles to tasks spawned before the fork never finish in the
+/// child.
+#[pyfunction]
+pub fn reset_after_fork() {
+    // The forking thread is the only one the child has, and with the hooks in place it took the
+    // lock before the fork, so the lock it finds here is one it holds itself.
+    let inherited: Option<RwLockWriteGuard<'static, Option<Runtime>>> =
+        FORK_GUARD.with(|held: &RefCell<Option<RwLockWriteGuard<'static, Option<Runtime>>>>| held.borrow_mut().take());
+
+    match inherited {
+        Some(mut slot) => discard_inherited_state(&mut slot),
+        None => discard_inherited_state(&mut runtime_slot_mut()),
+    }
+}
+
+/// Makes coil's runtime the current tokio runtime on this thread while it is entered, for
+/// libraries that look it up with `Handle::current()`.
+#[pyclass(name = "RuntimeContext", unsendable)]
+pub struct PyRuntimeContext {
+    // Declared before `handle` so it is dropped first; it borrows from it.
+    guard: Option<EnterGuard<'static>>,
+    handle: Option<Box<Handle>>,
+}
+
+#[pymethods]
+impl PyRuntimeContext {
+    fn __enter__(mut slf: PyRefMut<'_, Self>) -> PyResult<PyRefMut<'_, Self>> {
+        if slf.guard.is_some() {
+            return Err(PyErr::new::<PyRuntimeError, _>("This runtime context has already been entered."));
+        }
+
+        let handle: Box<Handle> = Box::new(handle()?);
+
+        // Safety: the box gives the handle a stable address, and the guard borrowing it is always
+        // dropped before the box (in `__exit__`, or by field order when the context is dropped).
+        let guard: EnterGuard<'static> = unsafe { &*(handle.as_ref() as *const Handle) }.enter();
+
+        slf.handle = Some(handle);
+        slf.guard = Some(guard);
+
+        Ok(slf)
+    }
+
+    #[pyo3(signature = (*_exc_info))]
+    fn __exit__(&mut self, _exc_info: &Bound<'_, PyTuple>) -> bool {
+        self.guard = None;
+        self.handle = None;
+
+        false
+    }
+}
+
+/// Returns a context manager that makes coil's runtime the current tokio runtime on the calling
+/// thread inside its block, so other tokio-based extensions in the process can find and use it.
+/// Contexts must be exited in the reverse order they were entered, and on the same thread.
+#[pyfunction]
+pub fn runtime_context() -> PyRuntimeContext {
+    PyRuntimeContext { guard: None, handle: None }
+}
@@ -0,0 +1,307 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyFunction};
+use tokio::sync::Notify;
+
+use crate::gil;
+use crate::internal;
+use crate::limit;
+use crate::runtime;
+use crate::task::PyTaskHandle;
+
+static NEXT_SCHEDULE_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Default)]
+struct ScheduleState {
+    // On `runtime::now_ns`'s clock. `None` while a run is in progress and once the schedule is done.
+    next_run_ns: Option<u64>,
+    runs: u64,
+    cancelled: bool,
+    // The exception that stopped the schedule, if one did.
+    error: Option<PyErr>,
+    done: bool,
+}
+
+struct ScheduleInner {
+    id: u64,
+    name: String,
+    state: Mutex<ScheduleState>,
+    finished: Notify,
+    task: OnceLock<PyTaskHandle>,
+}
+
+impl ScheduleInner {
+    fn state(&self) -> MutexGuard<'_, ScheduleState> {
+        self.state.lock().expect("Schedule state mutex was poisoned.")
+    }
+
+    // Returns whether this call is the one that ended the schedule.
+    fn finish(&self, error: Option<PyErr>, cancelled: bool) -> bool {
+        {
+            let mut state: MutexGuard<'_, ScheduleState> = self.state();
+
+            if state.done {
+                return false
+            }
+
+            state.done = true;
+            state.cancelled = cancelled;
+            state.error = error;
+            state.next_run_ns = None;
+        }
+
+        self.finished.notify_waiters();
+
+        true
+    }
+
+    async fn wait(&self) {
+        loop {
+            let notified = self.finished.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if self.state().done {
+                break
+            }
+
+            notified.await;
+        }
+    }
+}
+
+struct Schedule {
+    delay: Duration,
+    period: Option<Duration>,
+    jitter: Option<Duration>,
+    max_runs: Option<u64>,
+}
+
+// A random extra delay of up to `jitter`. There is no RNG among the dependencies; `RandomState`
+// is seeded randomly for each instance, which is plenty for spreading runs out.
+fn jitter_ns(jitter: Option<Duration>) -> u64 {
+    match jitter {
+        Some(jitter) if !jitter.is_zero() => RandomState::new().build_hasher().finish() % (jitter.as_nanos() as u64 + 1),
+        _ => 0,
+    }
+}
+
+fn duration_ns(duration: Duration) -> u64 {
+    duration.as_nanos().min(u64::MAX as u128) as u64
+}
+
+// `due_ns` is where the first run falls without jitter. Each later one is due a period after the
+// last, and a schedule that fell behind picks up from now rather than running back to back to
+// catch up.
+async fn run_schedule(inner: Arc<ScheduleInner>, py_func: Py<PyFunction>, arg: Py<PyAny>, schedule: Schedule, mut due_ns: u64) {
+    let py_func: Arc<Py<PyFunction>> = Arc::new(py_func);
+    let arg: Arc<Py<PyAny>> = Arc::new(arg);
+
+    loop {
+        // Set up front for the first run, so it is there before this task gets going. Gone if the
+        // schedule was cancelled since.
+        let Some(run_at_ns) = inner.state().next_run_ns else { return };
+
+        tokio::time::sleep_until(runtime::instant_at(run_at_ns)).await;
+        inner.state().next_run_ns = None;
+
+        let result: PyResult<PyObject> = match limit::wait_for_slot().await {
+            Ok(_slot) => internal::exe_python_callable_async(py_func.clone(), arg.clone()).await,
+            Err(error) => Err(error),
+        };
+
+        let runs: u64 = {
+            let mut state: MutexGuard<'_, ScheduleState> = inner.state();
+            state.runs += 1;
+            state.runs
+        };
+
+        if let Err(error) = result {
+            inner.finish(Some(error), false);
+            return
+        }
+
+        let Some(period) = schedule.period else { break };
+
+        if schedule.max_runs.is_some_and(|max_runs: u64| runs >= max_runs) {
+            break
+        }
+
+        due_ns = due_ns.saturating_add(duration_ns(period)).max(runtime::now_ns());
+
+        let mut state: MutexGuard<'_, ScheduleState> = inner.state();
+
+        if state.done {
+            return
+        }
+
+        state.next_run_ns = Some(due_ns.saturating_add(jitter_ns(schedule.jitter)));
+    }
+
+    inner.finish(None, false);
+}
+
+fn seconds(name: &str, value: f64) -> PyResult<Duration> {
+    Duration::try_from_secs_f64(value)
+        .map_err(|_| PyErr::new::<PyValueError, _>(format!("'{name}' must be a finite, non-negative number, got {value}.")))
+}
+
+fn start(py: Python<'_>, py_func: Py<PyFunction>, arg: Py<PyAny>, schedule: Schedule) -> PyResult<PyScheduledTask> {
+    internal::setup_python_path(py)?;
+
+    let due_ns: u64 = runtime::now_ns().saturating_add(duration_ns(schedule.delay));
+    let state: ScheduleState = ScheduleState {
+        next_run_ns: Some(due_ns.saturating_add(jitter_ns(schedule.jitter))),
+        ..ScheduleState::default()
+    };
+
+    let inner: Arc<ScheduleInner> = Arc::new(ScheduleInner {
+        id: NEXT_SCHEDULE_ID.fetch_add(1, Ordering::Relaxed),
+        name: internal::callable_name(py_func.bind(py)),
+        state: Mutex::new(state),
+        finished: Notify::new(),
+        task: OnceLock::new(),
+    });
+
+    // Runs as a task of its own, so each call gets a task id like a spawned one does.
+    let schedule_inner: Arc<ScheduleInner> = inner.clone();
+    let task: PyTaskHandle = PyTaskHandle::spawn(inner.name.clone(), async move {
+        run_schedule(schedule_inner, py_func, arg, schedule, due_ns).await;
+
+        gil::attach(|py| py.None())
+    })?;
+    let _ = inner.task.set(task);
+
+    Ok(PyScheduledTask { inner })
+}
+
+/// Calls `py_func(arg)` once, `delay` seconds from now, on one of coil's threads.
+#[pyfunction]
+pub fn schedule_once(py: Python<'_>, py_func: Py<PyFunction>, arg: Py<PyAny>, delay: f64) -> PyResult<PyScheduledTask> {
+    let schedule: Schedule = Schedule { delay: seconds("delay", delay)?, period: None, jitter: None, max_runs: None };
+
+    start(py, py_func, arg, schedule)
+}
+
+/// Calls `py_func(arg)` every `period` seconds, starting one period from now, until cancelled,
+/// until it has run `max_runs` times, or until a call raises. A run that overlaps the next one's
+/// time delays it rather than running alongside it. With `jitter`, each run is pushed back by a
+/// random amount of up to that many seconds, so schedules started together drift apart.
+#[pyfunction]
+#[pyo3(signature = (py_func, arg, period, *, jitter = None, max_runs = None))]
+pub fn schedule_interval(
+    py: Python<'_>,
+    py_func: Py<PyFunction>,
+    arg: Py<PyAny>,
+    period: f64,
+    jitter: Option<f64>,
+    max_runs: Option<u64>,
+) -> PyResult<PyScheduledTask> {
+    let period: Duration = seconds("period", period)?;
+
+    if period.is_zero() {
+        return Err(PyErr::new::<PyValueError, _>("'period' must be greater than zero."));
+    }
+
+    if max_runs == Some(0) {
+        return Err(PyErr::new::<PyValueError, _>("'max_runs' must be at least 1."));
+    }
+
+    let schedule: Schedule = Schedule {
+        delay: period,
+        period: Some(period),
+        jitter: jitter.map(|jitter: f64| seconds("jitter", jitter)).transpose()?,
+        max_runs,
+    };
+
+    start(py, py_func, arg, schedule)
+}
+
+/// A handle to a schedule started with `schedule_once` or `schedule_interval`. A schedule that
+/// stops, because it was cancelled, ran out of runs or a call raised, stays inspectable here.
+#[pyclass(name = "ScheduledTask")]
+#[derive(Clone)]
+pub struct PyScheduledTask {
+    inner: Arc<ScheduleInner>,
+}
+
+#[pymethods]
+impl PyScheduledTask {
+    /// Stops any further runs, returning `False` if the schedule had already stopped. A run in
+    /// progress finishes in the background.
+    pub fn cancel(&self) -> bool {
+        let cancelled: bool = self.inner.finish(None, true);
+
+        if cancelled && let Some(task) = self.inner.task.get() {
+            task.cancel();
+        }
+
+        cancelled
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.inner.state().cancelled
+    }
+
+    /// Whether the schedule has stopped for any reason.
+    pub fn done(&self) -> bool {
+        self.inner.state().done
+    }
+
+    /// Seconds until the next run, `0.0` if it is due, or `None` while a run is in progress and
+    /// once the schedule has stopped.
+    pub fn next_run(&self) -> Option<f64> {
+        let next_run_ns: u64 = self.inner.state().next_run_ns?;
+
+        Some(Duration::from_nanos(next_run_ns.saturating_sub(runtime::now_ns())).as_secs_f64())
+    }
+
+    /// How many times the callable has been called so far.
+    #[getter]
+    pub fn runs(&self) -> u64 {
+        self.inner.state().runs
+    }
+
+    /// The exception that stopped the schedule, or `None`.
+    pub fn exception(&self, py: Python<'_>) -> Option<PyObject> {
+        self.inner.state().error.as_ref().map(|error: &PyErr| error.value(py).clone().into_any().unbind())
+    }
+
+    /// Waits, with the GIL released, until the schedule stops, for at most `timeout` seconds if
+    /// given. Returns whether it has stopped.
+    #[pyo3(signature = (timeout = None))]
+    pub fn wait(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<bool> {
+        let timeout: Option<Duration> = timeout.map(|timeout: f64| seconds("timeout", timeout.max(0.0))).transpose()?;
+        let inner: Arc<ScheduleInner> = self.inner.clone();
+
+        py.allow_threads(move || {
+            runtime::block_on(async move {
+                match timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, inner.wait()).await.is_ok(),
+                    None => {
+                        inner.wait().await;
+                        true
+                    }
+                }
+            })
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        let state: MutexGuard<'_, ScheduleState> = self.inner.state();
+        let status: &str = match (state.done, state.cancelled, state.error.is_some()) {
+            (false, _, _) => "scheduled",
+            (true, true, _) => "cancelled",
+            (true, false, true) => "failed",
+            (true, false, false) => "finished",
+        };
+
+        format!("ScheduledTask(id={}, name={:?}, runs={}, {status})", self.inner.id, self.inner.name, state.runs)
+    }
+}
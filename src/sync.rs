@@ -0,0 +1,811 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyModule};
+use tokio::sync::{AcquireError, Notify, OwnedRwLockReadGuard, OwnedRwLockWriteGuard, OwnedSemaphorePermit, RwLock, Semaphore};
+
+use crate::errors::LockError;
+use crate::runtime;
+use crate::PyMutexLock;
+
+#[pyclass(name = "AtomicCounter")]
+#[derive(Clone)]
+pub struct PyAtomicCounter {
+    value: Arc<AtomicI64>,
+}
+
+#[pymethods]
+impl PyAtomicCounter {
+    #[new]
+    #[pyo3(signature = (value = 0))]
+    fn new(value: i64) -> Self {
+        Self {
+            value: Arc::new(AtomicI64::new(value)),
+        }
+    }
+
+    /// Adds `n` and returns the new value.
+    #[pyo3(signature = (n = 1))]
+    pub fn increment(&self, n: i64) -> i64 {
+        self.value.fetch_add(n, Ordering::SeqCst).wrapping_add(n)
+    }
+
+    /// Subtracts `n` and returns the new value.
+    #[pyo3(signature = (n = 1))]
+    pub fn decrement(&self, n: i64) -> i64 {
+        self.value.fetch_sub(n, Ordering::SeqCst).wrapping_sub(n)
+    }
+
+    pub fn get(&self) -> i64 {
+        self.value.load(Ordering::SeqCst)
+    }
+
+    pub fn set(&self, value: i64) {
+        self.value.store(value, Ordering::SeqCst);
+    }
+
+    /// Stores `new` only if the current value is `expected`, returning whether it did.
+    pub fn compare_and_swap(&self, expected: i64, new: i64) -> bool {
+        self.value.compare_exchange(expected, new, Ordering::SeqCst, Ordering::SeqCst).is_ok()
+    }
+
+    fn __int__(&self) -> i64 {
+        self.get()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("AtomicCounter({})", self.get())
+    }
+}
+
+/// A counting semaphore over `tokio::sync::Semaphore`. Waiters are served in the order they
+/// arrived, including ones asking for several permits at once, which hold back any that came
+/// after them.
+#[pyclass(name = "Semaphore")]
+#[derive(Clone)]
+pub struct PySemaphore {
+    inner: Arc<Semaphore>,
+}
+
+impl PySemaphore {
+    /// Waits for a permit with the GIL released. The permit goes back to the semaphore when it is
+    /// dropped, which lets Rust-side users tie it to the lifetime of a task.
+    pub fn acquire_owned(&self, py: Python<'_>) -> PyResult<OwnedSemaphorePermit> {
+        let inner: Arc<Semaphore> = self.inner.clone();
+
+        py.allow_threads(move || runtime::block_on(inner.acquire_owned()))?
+            .map_err(|e: AcquireError| PyErr::new::<PyRuntimeError, _>(format!("Failed to acquire semaphore: {}", e)))
+    }
+}
+
+fn permit_count(n: usize) -> PyResult<u32> {
+    u32::try_from(n)
+        .map_err(|_| PyErr::new::<PyValueError, _>(format!("Can't take {n} permits at once, the most is {}.", u32::MAX)))
+}
+
+#[pymethods]
+impl PySemaphore {
+    #[new]
+    fn new(permits: usize) -> Self {
+        Self {
+            inner: Arc::new(Semaphore::new(permits)),
+        }
+    }
+
+    /// Waits, with the GIL released, until `n` permits are available and takes them.
+    #[pyo3(signature = (n = 1))]
+    pub fn acquire(&self, py: Python<'_>, n: usize) -> PyResult<()> {
+        if n == 1 {
+            self.acquire_owned(py)?.forget();
+            return Ok(());
+        }
+
+        let n: u32 = permit_count(n)?;
+        let inner: Arc<Semaphore> = self.inner.clone();
+
+        py.allow_threads(move || runtime::block_on(inner.acquire_many_owned(n)))?
+            .map_err(|e: AcquireError| PyErr::new::<PyRuntimeError, _>(format!("Failed to acquire semaphore: {}", e)))?
+            .forget();
+
+        Ok(())
+    }
+
+    /// Takes `n` permits if they are available right now, returning whether it did.
+    #[pyo3(signature = (n = 1))]
+    pub fn try_acquire(&self, n: usize) -> PyResult<bool> {
+        match self.inner.try_acquire_many(permit_count(n)?) {
+            Ok(permit) => {
+                permit.forget();
+                Ok(true)
+            }
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Gives back `n` permits. Nothing checks that they were taken first, so releasing more than
+    /// was acquired raises the semaphore's count.
+    #[pyo3(signature = (n = 1))]
+    pub fn release(&self, n: usize) -> PyResult<()> {
+        if n > Semaphore::MAX_PERMITS - self.inner.available_permits() {
+            return Err(PyErr::new::<PyValueError, _>(format!("Releasing {n} permits would overflow the semaphore.")));
+        }
+
+        self.inner.add_permits(n);
+
+        Ok(())
+    }
+
+    pub fn available_permits(&self) -> usize {
+        self.inner.available_permits()
+    }
+
+    fn __enter__(&self, py: Python<'_>) -> PyResult<()> {
+        self.acquire(py, 1)
+    }
+
+    fn __exit__(
+        &self,
+        _exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        self.release(1)?;
+
+        Ok(false)
+    }
+}
+
+struct RwLockInner {
+    lock: Arc<RwLock<()>>,
+    // Guards for the read locks currently held, in no particular order since they are all alike.
+    readers: Mutex<Vec<OwnedRwLockReadGuard<()>>>,
+    writer: Mutex<Option<OwnedRwLockWriteGuard<()>>>,
+}
+
+impl RwLockInner {
+    fn readers(&self) -> MutexGuard<'_, Vec<OwnedRwLockReadGuard<()>>> {
+        self.readers.lock().expect("RwLock readers mutex was poisoned.")
+    }
+
+    fn writer(&self) -> MutexGuard<'_, Option<OwnedRwLockWriteGuard<()>>> {
+        self.writer.lock().expect("RwLock writer mutex was poisoned.")
+    }
+}
+
+/// A readers-writer lock over `tokio::sync::RwLock`: any number of readers or a single writer.
+/// It is fair, so once a writer is waiting, new readers queue up behind it; a thread that tries
+/// to take a second read lock while a writer waits deadlocks. Locks aren't tied to the thread
+/// that took them, and any thread may release one.
+#[pyclass(name = "RwLock")]
+#[derive(Clone)]
+pub struct PyRwLock {
+    inner: Arc<RwLockInner>,
+}
+
+#[pymethods]
+impl PyRwLock {
+    #[new]
+    fn new() -> Self {
+        Self {
+            inner: Arc::new(RwLockInner {
+                lock: Arc::new(RwLock::new(())),
+                readers: Mutex::new(Vec::new()),
+                writer: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Waits, with the GIL released, for a read lock.
+    pub fn acquire_read(&self, py: Python<'_>) -> PyResult<()> {
+        let lock: Arc<RwLock<()>> = self.inner.lock.clone();
+        let guard: OwnedRwLockReadGuard<()> = py.allow_threads(move || runtime::block_on(lock.read_owned()))?;
+
+        self.inner.readers().push(guard);
+
+        Ok(())
+    }
+
+    /// Waits, with the GIL released, until no one else holds the lock and takes it for writing.
+    pub fn acquire_write(&self, py: Python<'_>) -> PyResult<()> {
+        let lock: Arc<RwLock<()>> = self.inner.lock.clone();
+        let guard: OwnedRwLockWriteGuard<()> = py.allow_threads(move || runtime::block_on(lock.write_owned()))?;
+
+        *self.inner.writer() = Some(guard);
+
+        Ok(())
+    }
+
+    pub fn try_acquire_read(&self) -> bool {
+        match self.inner.lock.clone().try_read_owned() {
+            Ok(guard) => {
+                self.inner.readers().push(guard);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    pub fn try_acquire_write(&self) -> bool {
+        match self.inner.lock.clone().try_write_owned() {
+            Ok(guard) => {
+                *self.inner.writer() = Some(guard);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Gives back one read lock. Raises `LockError` if none is held.
+    pub fn release_read(&self) -> PyResult<()> {
+        match self.inner.readers().pop() {
+            Some(_) => Ok(()),
+            None => Err(PyErr::new::<LockError, _>("The RwLock is not locked for reading.")),
+        }
+    }
+
+    /// Gives back the write lock. Raises `LockError` if it isn't held.
+    pub fn release_write(&self) -> PyResult<()> {
+        match self.inner.writer().take() {
+            Some(_) => Ok(()),
+            None => Err(PyErr::new::<LockError, _>("The RwLock is not locked for writing.")),
+        }
+    }
+
+    /// How many read locks are held right now.
+    #[getter]
+    pub fn readers(&self) -> usize {
+        self.inner.readers().len()
+    }
+
+    pub fn is_write_locked(&self) -> bool {
+        self.inner.writer().is_some()
+    }
+
+    /// `with lock.read(): ...` holds a read lock for the duration of the block.
+    pub fn read(&self) -> PyRwLockGuard {
+        PyRwLockGuard { lock: self.clone(), write: false }
+    }
+
+    /// `with lock.write(): ...` holds the write lock for the duration of the block.
+    pub fn write(&self) -> PyRwLockGuard {
+        PyRwLockGuard { lock: self.clone(), write: true }
+    }
+}
+
+#[pyclass(name = "RwLockGuard")]
+pub struct PyRwLockGuard {
+    lock: PyRwLock,
+    write: bool,
+}
+
+#[pymethods]
+impl PyRwLockGuard {
+    fn __enter__(&self, py: Python<'_>) -> PyResult<()> {
+        match self.write {
+            true => self.lock.acquire_write(py),
+            false => self.lock.acquire_read(py),
+        }
+    }
+
+    fn __exit__(
+        &self,
+        _exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        match self.write {
+            true => self.lock.release_write()?,
+            false => self.lock.release_read()?,
+        }
+
+        Ok(false)
+    }
+}
+
+struct ConditionInner {
+    lock: PyMutexLock,
+    // One entry per thread in `wait`, oldest first. Each has its own `Notify`, so a wakeup always
+    // reaches the waiter it was meant for, even if it hasn't started listening yet.
+    waiters: Mutex<VecDeque<Arc<Notify>>>,
+}
+
+impl ConditionInner {
+    fn waiters(&self) -> MutexGuard<'_, VecDeque<Arc<Notify>>> {
+        self.waiters.lock().expect("Condition waiters mutex was poisoned.")
+    }
+}
+
+fn condition_timeout(seconds: f64) -> PyResult<Duration> {
+    Duration::try_from_secs_f64(seconds.max(0.0))
+        .map_err(|_| PyErr::new::<PyValueError, _>(format!("'timeout' must be a finite number, got {seconds}.")))
+}
+
+/// A condition variable, like `threading.Condition`, over a `MutexLock` (a new one unless `lock`
+/// is given). Waiting releases the lock and blocks with the GIL released until notified, then
+/// takes the lock back before returning. Timeouts are in seconds.
+#[pyclass(name = "Condition")]
+#[derive(Clone)]
+pub struct PyCondition {
+    inner: Arc<ConditionInner>,
+}
+
+impl PyCondition {
+    fn check_owned(&self, action: &str) -> PyResult<()> {
+        match self.inner.lock.is_owned() {
+            true => Ok(()),
+            false => Err(PyErr::new::<LockError, _>(format!("Can't {action} a Condition without holding its lock."))),
+        }
+    }
+
+    fn wait_for_notify(&self, py: Python<'_>, timeout: Option<Duration>) -> PyResult<bool> {
+        self.check_owned("wait on")?;
+
+        let waiter: Arc<Notify> = Arc::new(Notify::new());
+        self.inner.waiters().push_back(waiter.clone());
+        self.inner.lock.release(py)?;
+
+        let waited: Arc<Notify> = waiter.clone();
+        let notified: PyResult<bool> = py.allow_threads(move || {
+            runtime::block_on(async move {
+                match timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, waited.notified()).await.is_ok(),
+                    None => {
+                        waited.notified().await;
+                        true
+                    }
+                }
+            })
+        });
+
+        // A waiter still in the queue wasn't notified. One that isn't was, even if that happened
+        // just as it timed out or the wait was cut short.
+        let queued: bool = match notified {
+            Ok(true) => false,
+            _ => {
+                let mut waiters: MutexGuard<'_, VecDeque<Arc<Notify>>> = self.inner.waiters();
+
+                match waiters.iter().position(|queued| Arc::ptr_eq(queued, &waiter)) {
+                    Some(index) => {
+                        waiters.remove(index);
+                        true
+                    }
+                    None => {
+                        // It won't act on the notification it was given, so that goes to the
+                        // next waiter instead.
+                        if notified.is_err()
+                            && let Some(next) = waiters.pop_front()
+                        {
+                            next.notify_one();
+                        }
+
+                        false
+                    }
+                }
+            }
+        };
+
+        self.inner.lock.acquire(py)?;
+
+        // Only running out of time returns `False`; anything else that ended the wait is raised.
+        Ok(notified? || !queued)
+    }
+}
+
+#[pymethods]
+impl PyCondition {
+    #[new]
+    #[pyo3(signature = (lock = None))]
+    fn new(lock: Option<PyMutexLock>) -> Self {
+        Self {
+            inner: Arc::new(ConditionInner {
+                lock: lock.unwrap_or_default(),
+                waiters: Mutex::new(VecDeque::new()),
+            }),
+        }
+    }
+
+    pub fn acquire(&self, py: Python<'_>) -> PyResult<()> {
+        self.inner.lock.acquire(py)
+    }
+
+    pub fn release(&self, py: Python<'_>) -> PyResult<()> {
+        self.inner.lock.release(py)
+    }
+
+    /// Releases the lock, waits until notified or until `timeout` runs out, and takes the lock
+    /// back. Returns `False` if it timed out. Raises `LockError` unless the caller holds the lock.
+    #[pyo3(signature = (timeout = None))]
+    pub fn wait(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<bool> {
+        let timeout: Option<Duration> = timeout.map(condition_timeout).transpose()?;
+
+        self.wait_for_notify(py, timeout)
+    }
+
+    /// Waits until `predicate()` is true, for at most `timeout` seconds in all, and returns its
+    /// last result. The predicate is called with the lock held.
+    #[pyo3(signature = (predicate, timeout = None))]
+    pub fn wait_for(&self, py: Python<'_>, predicate: Py<PyAny>, timeout: Option<f64>) -> PyResult<PyObject> {
+        let deadline: Option<tokio::time::Instant> = timeout
+            .map(condition_timeout)
+            .transpose()?
+            .map(|timeout: Duration| tokio::time::Instant::now() + timeout);
+
+        loop {
+            let result: PyObject = predicate.call0(py)?;
+
+            if result.is_truthy(py)? {
+                return Ok(result);
+            }
+
+            let remaining: Option<Duration> = deadline.map(|deadline| deadline.saturating_duration_since(tokio::time::Instant::now()));
+
+            if remaining.is_some_and(|remaining: Duration| remaining.is_zero()) {
+                return Ok(result);
+            }
+
+            self.wait_for_notify(py, remaining)?;
+        }
+    }
+
+    /// Wakes up to `n` of the threads waiting, longest-waiting first. Raises `LockError` unless
+    /// the caller holds the lock.
+    #[pyo3(signature = (n = 1))]
+    pub fn notify(&self, n: usize) -> PyResult<()> {
+        self.check_owned("notify")?;
+
+        let mut waiters: MutexGuard<'_, VecDeque<Arc<Notify>>> = self.inner.waiters();
+
+        let woken: usize = n.min(waiters.len());
+
+        for waiter in waiters.drain(..woken) {
+            waiter.notify_one();
+        }
+
+        Ok(())
+    }
+
+    /// Wakes every thread waiting.
+    pub fn notify_all(&self) -> PyResult<()> {
+        self.notify(usize::MAX)
+    }
+
+    fn __enter__(&self, py: Python<'_>) -> PyResult<()> {
+        self.acquire(py)
+    }
+
+    fn __exit__(
+        &self,
+        py: Python<'_>,
+        _exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        self.release(py)?;
+
+        Ok(false)
+    }
+}
+
+/// A fixed set of independent mutexes with keys hashed onto them, so operations on different keys
+/// rarely contend while operations on the same key are still mutually exclusive.
+#[pyclass(name = "ShardedLock")]
+#[derive(Clone)]
+pub struct PyShardedLock {
+    shards: Arc<Vec<PyMutexLock>>,
+}
+
+impl PyShardedLock {
+    fn shard(&self, key: &Bound<'_, PyAny>) -> PyResult<&PyMutexLock> {
+        let index: usize = (key.hash()? as u64 % self.shards.len() as u64) as usize;
+
+        Ok(&self.shards[index])
+    }
+}
+
+#[pymethods]
+impl PyShardedLock {
+    #[new]
+    fn new(num_shards: usize) -> PyResult<Self> {
+        if num_shards == 0 {
+            return Err(PyErr::new::<PyValueError, _>("A ShardedLock needs at least one shard."));
+        }
+
+        Ok(Self {
+            shards: Arc::new((0..num_shards).map(|_| PyMutexLock::default()).collect()),
+        })
+    }
+
+    pub fn acquire(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.shard(key)?.acquire(py)
+    }
+
+    pub fn release(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.shard(key)?.release(py)
+    }
+
+    pub fn get_locked(&self, key: &Bound<'_, PyAny>) -> PyResult<bool> {
+        Ok(self.shard(key)?.is_locked())
+    }
+
+    #[getter]
+    pub fn num_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// `with sharded(key): ...` holds the shard that `key` maps to for the duration of the block.
+    fn __call__(&self, key: &Bound<'_, PyAny>) -> PyResult<PyShardGuard> {
+        Ok(PyShardGuard {
+            lock: self.shard(key)?.clone(),
+        })
+    }
+}
+
+#[pyclass(name = "ShardGuard")]
+pub struct PyShardGuard {
+    lock: PyMutexLock,
+}
+
+#[pymethods]
+impl PyShardGuard {
+    fn __enter__(&self, py: Python<'_>) -> PyResult<()> {
+        self.lock.acquire(py)
+    }
+
+    fn __exit__(
+        &self,
+        py: Python<'_>,
+        _exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        self.lock.release(py)?;
+
+        Ok(false)
+    }
+}
+
+/// A lock that busy-waits instead of parking. Only worth it for critical sections a few
+/// instructions long: waiters burn a full core for as long as the lock is held, and a holder that
+/// blocks or gets descheduled leaves them all spinning. Use `MutexLock` for anything else.
+#[pyclass(name = "SpinLock")]
+#[derive(Clone, Default)]
+pub struct PySpinLock {
+    locked: Arc<AtomicBool>,
+}
+
+impl PySpinLock {
+    fn try_lock(&self) -> bool {
+        self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok()
+    }
+}
+
+#[pymethods]
+impl PySpinLock {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn acquire(&self, py: Python<'_>) {
+        if self.try_lock() {
+            return
+        }
+
+        // The holder may need the GIL to get to its release, so it can't be kept while spinning.
+        py.allow_threads(|| loop {
+            while self.locked.load(Ordering::Relaxed) {
+                std::hint::spin_loop();
+            }
+
+            if self.try_lock() {
+                break
+            }
+        });
+    }
+
+    pub fn try_acquire(&self) -> bool {
+        self.try_lock()
+    }
+
+    pub fn release(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+
+    pub fn get_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    fn __enter__(&self, py: Python<'_>) {
+        self.acquire(py);
+    }
+
+    fn __exit__(
+        &self,
+        _exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> bool {
+        self.release();
+
+        false
+    }
+}
+
+// One round of a `Barrier`. Every waiter of the round holds it, so each finds out how its own
+// round ended even after the barrier has moved on to the next one.
+struct BarrierRound {
+    // `true` once every party arrived, `false` if the round was broken.
+    tripped: OnceLock<bool>,
+    done: Notify,
+}
+
+impl BarrierRound {
+    fn new() -> Arc<Self> {
+        Arc::new(Self { tripped: OnceLock::new(), done: Notify::new() })
+    }
+
+    fn finish(&self, tripped: bool) {
+        let _ = self.tripped.set(tripped);
+        self.done.notify_waiters();
+    }
+
+    async fn wait(&self) -> bool {
+        loop {
+            let notified = self.done.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if let Some(tripped) = self.tripped.get() {
+                return *tripped
+            }
+
+            notified.await;
+        }
+    }
+}
+
+struct BarrierState {
+    round: Arc<BarrierRound>,
+    arrived: usize,
+}
+
+struct BarrierInner {
+    state: Mutex<BarrierState>,
+    parties: usize,
+}
+
+impl BarrierInner {
+    fn state(&self) -> MutexGuard<'_, BarrierState> {
+        self.state.lock().expect("Barrier state mutex was poisoned.")
+    }
+}
+
+fn broken_barrier_error(py: Python<'_>) -> PyErr {
+    match PyModule::import(py, "threading").and_then(|module| module.getattr("BrokenBarrierError")) {
+        Ok(broken) => PyErr::from_value(broken),
+        Err(error) => error,
+    }
+}
+
+/// Blocks callers until `parties` of them are waiting, then lets them all through at once and
+/// starts over. A caller that gives up with `wait_timeout` breaks the barrier: everyone waiting
+/// on it, and everyone who waits on it afterwards, raises `threading.BrokenBarrierError` until
+/// `reset` is called.
+#[pyclass(name = "Barrier")]
+#[derive(Clone)]
+pub struct PyBarrier {
+    inner: Arc<BarrierInner>,
+}
+
+impl PyBarrier {
+    // `None` if `timeout` ran out first, in which case the barrier is now broken.
+    fn arrive(&self, py: Python<'_>, timeout: Option<Duration>) -> PyResult<Option<bool>> {
+        let round: Arc<BarrierRound> = {
+            let mut state: MutexGuard<'_, BarrierState> = self.inner.state();
+
+            if state.round.tripped.get() == Some(&false) {
+                return Err(broken_barrier_error(py));
+            }
+
+            state.arrived += 1;
+
+            if state.arrived == self.inner.parties {
+                state.round.finish(true);
+                state.round = BarrierRound::new();
+                state.arrived = 0;
+
+                return Ok(Some(true));
+            }
+
+            state.round.clone()
+        };
+
+        let waited: Arc<BarrierRound> = round.clone();
+        let tripped: Option<bool> = py.allow_threads(move || {
+            runtime::block_on(async move {
+                match timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, waited.wait()).await.ok(),
+                    None => Some(waited.wait().await),
+                }
+            })
+        })?;
+
+        let tripped: bool = match tripped {
+            Some(tripped) => tripped,
+            None => {
+                // The round may have ended just as the timeout did; whoever ends it first decides.
+                let _state: MutexGuard<'_, BarrierState> = self.inner.state();
+
+                if round.tripped.get().is_none() {
+                    round.finish(false);
+                    return Ok(None);
+                }
+
+                round.tripped.get() == Some(&true)
+            }
+        };
+
+        match tripped {
+            true => Ok(Some(false)),
+            false => Err(broken_barrier_error(py)),
+        }
+    }
+}
+
+#[pymethods]
+impl PyBarrier {
+    #[new]
+    fn new(parties: usize) -> PyResult<Self> {
+        if parties == 0 {
+            return Err(PyErr::new::<PyValueError, _>("A Barrier needs at least one party."));
+        }
+
+        Ok(Self {
+            inner: Arc::new(BarrierInner {
+                state: Mutex::new(BarrierState { round: BarrierRound::new(), arrived: 0 }),
+                parties,
+            }),
+        })
+    }
+
+    /// Waits, with the GIL released, for the rest of the parties. Returns `True` for exactly one
+    /// of them (the last to arrive) and `False` for the others.
+    pub fn wait(&self, py: Python<'_>) -> PyResult<bool> {
+        Ok(self.arrive(py, None)?.expect("Waiting without a timeout always finishes the round."))
+    }
+
+    /// Like `wait`, but gives up after `timeout_ns`, returning `None` and breaking the barrier.
+    pub fn wait_timeout(&self, py: Python<'_>, timeout_ns: u64) -> PyResult<Option<bool>> {
+        self.arrive(py, Some(Duration::from_nanos(timeout_ns)))
+    }
+
+    /// Puts the barrier back to its initial state. Anyone still waiting raises
+    /// `threading.BrokenBarrierError`.
+    pub fn reset(&self) {
+        let mut state: MutexGuard<'_, BarrierState> = self.inner.state();
+
+        state.round.finish(false);
+        state.round = BarrierRound::new();
+        state.arrived = 0;
+    }
+
+    #[getter]
+    pub fn parties(&self) -> usize {
+        self.inner.parties
+    }
+
+    /// How many parties are waiting in the current round.
+    #[getter]
+    pub fn n_waiting(&self) -> usize {
+        self.inner.state().arrived
+    }
+
+    #[getter]
+    pub fn broken(&self) -> bool {
+        self.inner.state().round.tripped.get() == Some(&false)
+    }
+}
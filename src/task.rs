@@ -0,0 +1,537 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, Weak};
+
+use once_cell::sync::Lazy;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict, PyList};
+use tokio::task::{AbortHandle, JoinError, JoinHandle, JoinSet};
+use tokio::time::error::Elapsed;
+use tokio::time::Duration;
+
+use crate::errors::{self, TaskCancelled, TaskPanicked, TaskTimeout};
+use crate::gil;
+use crate::runtime;
+
+pub type TaskOutput = PyResult<PyObject>;
+
+/// Turns a failed join into `TaskCancelled` or `TaskPanicked`, carrying the panic's message.
+pub fn join_error(error: JoinError) -> PyErr {
+    if error.is_cancelled() {
+        return PyErr::new::<TaskCancelled, _>("The task was cancelled.");
+    }
+
+    let payload: Box<dyn std::any::Any + Send> = error.into_panic();
+    let message: String = match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "The task panicked.".to_string(),
+        },
+    };
+
+    PyErr::new::<TaskPanicked, _>(message)
+}
+
+static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(1);
+
+tokio::task_local! {
+    static SCHEDULED_TASK: u64;
+}
+
+thread_local! {
+    static RUNNING_TASK: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// The id of the coil task whose future is being polled, if any. Blocking closures don't inherit
+/// task-locals, so anything that wants the id on the blocking thread reads it here first and
+/// hands it to `enter_task` there.
+pub fn scheduled_task_id() -> Option<u64> {
+    SCHEDULED_TASK.try_with(|id: &u64| *id).ok()
+}
+
+/// The id of the coil task running Python code on this thread, if any.
+pub fn current_task_id() -> Option<u64> {
+    RUNNING_TASK.with(Cell::get)
+}
+
+pub struct RunningTaskGuard {
+    previous: Option<u64>,
+}
+
+impl Drop for RunningTaskGuard {
+    fn drop(&mut self) {
+        RUNNING_TASK.with(|running: &Cell<Option<u64>>| running.set(self.previous));
+    }
+}
+
+/// Marks this thread as running `id` until the guard is dropped.
+pub fn enter_task(id: Option<u64>) -> RunningTaskGuard {
+    RunningTaskGuard {
+        previous: RUNNING_TASK.with(|running: &Cell<Option<u64>>| running.replace(id)),
+    }
+}
+
+// Every task spawned through coil, by id. Entries are weak: the state stays alive while the task
+// is running (its future holds a strong reference) or while Python holds a handle to it.
+static TASK_REGISTRY: Lazy<Mutex<HashMap<u64, Weak<TaskState>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn task_registry() -> MutexGuard<'static, HashMap<u64, Weak<TaskState>>> {
+    TASK_REGISTRY.lock().expect("Task registry mutex was poisoned.")
+}
+
+/// Forgets every task spawned so far. Only for a forked child, where none of them are running.
+pub fn reset() {
+    task_registry().clear();
+}
+
+pub fn was_issued(id: u64) -> bool {
+    id != 0 && id < NEXT_TASK_ID.load(Ordering::Relaxed)
+}
+
+/// Looks up a task by id. `None` means the id was never handed out, or the task has finished and
+/// every handle to it has been dropped.
+pub fn lookup_task(id: u64) -> Option<Arc<TaskState>> {
+    task_registry().get(&id).and_then(Weak::upgrade)
+}
+
+/// Every task that is still running, in spawn order.
+fn live_tasks() -> Vec<Arc<TaskState>> {
+    let mut tasks: Vec<Arc<TaskState>> = task_registry()
+        .values()
+        .filter_map(Weak::upgrade)
+        .filter(|state| !state.is_finished())
+        .collect();
+
+    tasks.sort_by_key(|state| state.id);
+    tasks
+}
+
+pub struct TaskState {
+    id: u64,
+    name: String,
+    join: tokio::sync::Mutex<Option<JoinHandle<TaskOutput>>>,
+    abort: OnceLock<AbortHandle>,
+    cancel_requested: AtomicBool,
+    outcome: Mutex<Option<TaskOutput>>,
+    // Tasks registered with `add_child`, which are cancelled along with this one.
+    children: Mutex<Vec<Weak<TaskState>>>,
+}
+
+impl Drop for TaskState {
+    fn drop(&mut self) {
+        task_registry().remove(&self.id);
+    }
+}
+
+impl TaskState {
+    fn outcome(&self) -> MutexGuard<'_, Option<TaskOutput>> {
+        self.outcome.lock().expect("Task outcome mutex was poisoned.")
+    }
+
+    /// Waits for the task to finish and stores its outcome. The `JoinHandle` is only taken
+    /// once it has resolved, so dropping this future early (a timeout, a cancelled gather) leaves
+    /// the task joinable by the next caller.
+    pub async fn wait(&self) {
+        let mut join = self.join.lock().await;
+
+        if let Some(handle) = join.as_mut() {
+            let output: Result<TaskOutput, JoinError> = handle.await;
+
+            *self.outcome() = Some(output.unwrap_or_else(|e: JoinError| Err(join_error(e))));
+            *join = None;
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.abort.get().is_some_and(AbortHandle::is_finished)
+    }
+
+    fn children(&self) -> MutexGuard<'_, Vec<Weak<TaskState>>> {
+        self.children.lock().expect("Task children mutex was poisoned.")
+    }
+
+    /// Aborts the task and, recursively, every child registered with it, returning whether the
+    /// task itself was still running.
+    pub fn cancel(&self) -> bool {
+        let running: bool = !self.is_finished();
+
+        self.cancel_requested.store(true, Ordering::SeqCst);
+
+        if let Some(abort) = self.abort.get() {
+            abort.abort();
+        }
+
+        let children: Vec<Weak<TaskState>> = std::mem::take(&mut *self.children());
+
+        for child in children.iter().filter_map(Weak::upgrade) {
+            child.cancel();
+        }
+
+        running
+    }
+
+    /// Makes `child` get cancelled whenever this task is. A child added after this task was
+    /// cancelled is cancelled straight away.
+    pub fn add_child(&self, child: &Arc<TaskState>) {
+        let mut children: MutexGuard<'_, Vec<Weak<TaskState>>> = self.children();
+
+        // Checked under the lock `cancel` takes after setting the flag, so the child is either
+        // seen by `cancel` or sees the flag here.
+        if self.cancel_requested.load(Ordering::SeqCst) {
+            drop(children);
+            child.cancel();
+            return
+        }
+
+        children.retain(|child: &Weak<TaskState>| child.upgrade().is_some_and(|child| !child.is_finished()));
+        children.push(Arc::downgrade(child));
+    }
+
+    /// Whether the task ended by raising. Only meaningful once `wait` has returned.
+    pub fn failed(&self) -> bool {
+        matches!(*self.outcome(), Some(Err(_)))
+    }
+
+    /// Clones the outcome out for the caller. Panics if `wait` has not completed yet.
+    pub fn output(&self, py: Python<'_>) -> TaskOutput {
+        match self.outcome().as_ref().expect("Task outcome read before the task finished.") {
+            Ok(value) => Ok(value.clone_ref(py)),
+            Err(error) => Err(error.clone_ref(py)),
+        }
+    }
+}
+
+// Hands a failure nobody can join to the exception handler, on the blocking pool since the
+// handler is arbitrary Python code.
+async fn report_unobserved(state: Arc<TaskState>, output: TaskOutput) -> TaskOutput {
+    tokio::task::spawn_blocking(move || {
+        let _ = gil::attach(|py| {
+            if let Err(error) = &output {
+                match Bound::new(py, PyTaskHandle { state }) {
+                    Ok(handle) => errors::report_error(py, error.clone_ref(py), handle.as_any()),
+                    Err(new_error) => new_error.print(py),
+                }
+            }
+        });
+
+        output
+    })
+    .await
+    .unwrap_or_else(|e: JoinError| Err(join_error(e)))
+}
+
+#[pyclass(name = "TaskHandle")]
+#[derive(Clone)]
+pub struct PyTaskHandle {
+    state: Arc<TaskState>,
+}
+
+impl PyTaskHandle {
+    pub fn spawn<F>(name: String, future: F) -> PyResult<Self>
+    where
+        F: Future<Output = TaskOutput> + Send + 'static,
+    {
+        Self::spawn_with(name, move |_| future)
+    }
+
+    /// Like `spawn`, but builds the future from the task's own handle, which exists (without its
+    /// `JoinHandle` yet) before the task starts.
+    pub fn spawn_with<F, B>(name: String, build: B) -> PyResult<Self>
+    where
+        B: FnOnce(PyTaskHandle) -> F,
+        F: Future<Output = TaskOutput> + Send + 'static,
+    {
+        let state: Arc<TaskState> = Arc::new(TaskState {
+            id: NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed),
+            name,
+            join: tokio::sync::Mutex::new(None),
+            abort: OnceLock::new(),
+            cancel_requested: AtomicBool::new(false),
+            outcome: Mutex::new(None),
+            children: Mutex::new(Vec::new()),
+        });
+        let keepalive: Arc<TaskState> = state.clone();
+        let future: F = build(Self { state: state.clone() });
+
+        let handle: JoinHandle<TaskOutput> = runtime::handle()?.spawn(SCHEDULED_TASK.scope(state.id, async move {
+            let output: TaskOutput = future.await;
+
+            // With no handle left, nothing can ever join the task, so a failure would go unseen.
+            if output.is_err() && Arc::strong_count(&keepalive) == 1 {
+                return report_unobserved(keepalive, output).await;
+            }
+
+            output
+        }));
+
+        // Nothing else can have seen the state yet, so the lock is always free here.
+        let _ = state.abort.set(handle.abort_handle());
+        *state.join.try_lock().expect("Task state was shared before being attached.") = Some(handle);
+
+        task_registry().insert(state.id, Arc::downgrade(&state));
+
+        Ok(Self { state })
+    }
+
+    pub fn state(&self) -> &Arc<TaskState> {
+        &self.state
+    }
+}
+
+#[pymethods]
+impl PyTaskHandle {
+    /// Blocks until the task finishes, returning what the callable returned or re-raising what it
+    /// raised. Raises `TaskPanicked` if the task panicked and `TaskCancelled` if it was cancelled.
+    /// With `timeout` (in seconds), raises `TaskTimeout` if the task is still running after that
+    /// long, as `join_timeout` does. Can be called any number of times.
+    #[pyo3(signature = (timeout = None))]
+    pub fn join(&self, py: Python<'_>, timeout: Option<f64>) -> TaskOutput {
+        if let Some(seconds) = timeout {
+            let timeout: Duration = Duration::try_from_secs_f64(seconds.max(0.0)).map_err(|_| {
+                PyErr::new::<PyValueError, _>(format!("'timeout' must be a finite number, got {seconds}."))
+            })?;
+
+            return self.join_timeout(py, timeout.as_nanos().min(u64::MAX as u128) as u64);
+        }
+
+        let state: Arc<TaskState> = self.state.clone();
+
+        py.allow_threads(move || runtime::block_on(state.wait()))?;
+
+        self.state.output(py)
+    }
+
+    /// Returns (or raises) the task's outcome like `join` without waiting for it, raising
+    /// `RuntimeError` if the task hasn't finished yet.
+    pub fn result(&self, py: Python<'_>) -> TaskOutput {
+        if !self.state.is_finished() {
+            return Err(PyErr::new::<PyRuntimeError, _>(format!("Task {} has not finished yet.", self.state.id)));
+        }
+
+        // Finished, so this only stores the outcome if nobody has yet.
+        self.join(py, None)
+    }
+
+    /// Like `join`, but raises `TaskTimeout` if the task is still running after `timeout_ns`.
+    /// The task keeps running and can be joined again afterwards.
+    pub fn join_timeout(&self, py: Python<'_>, timeout_ns: u64) -> TaskOutput {
+        let state: Arc<TaskState> = self.state.clone();
+        let timeout: Duration = Duration::from_nanos(timeout_ns);
+
+        py.allow_threads(move || runtime::block_on(async move { tokio::time::timeout(timeout, state.wait()).await }))?
+            .map_err(|_: Elapsed| {
+                PyErr::new::<TaskTimeout, _>(format!("Task {} did not finish within {:?}.", self.state.id, timeout))
+            })?;
+
+        self.state.output(py)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state.is_finished()
+    }
+
+    /// Cancels the task, so joining it raises `TaskCancelled`, along with every task spawned with
+    /// it as their `parent`, and theirs in turn; returns `False` if it had already finished. A
+    /// callable that has already started on the blocking pool can't be interrupted and runs to
+    /// completion in the background, but its result is discarded.
+    pub fn cancel(&self) -> bool {
+        self.state.cancel()
+    }
+
+    /// Whether `cancel` has been called. A callable given its own handle can poll this to stop
+    /// early, since cancelling can't interrupt it.
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancel_requested.load(Ordering::SeqCst)
+    }
+
+    #[getter]
+    pub fn id(&self) -> u64 {
+        self.state.id
+    }
+
+    #[getter]
+    pub fn name(&self) -> &str {
+        &self.state.name
+    }
+
+    fn __repr__(&self) -> String {
+        format!("TaskHandle(id={}, name={:?}, finished={})", self.state.id, self.state.name, self.is_finished())
+    }
+}
+
+// How often a gather takes the GIL back to check for signals while it waits.
+const SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Runs `future`, checking for pending signals every `SIGNAL_CHECK_INTERVAL`. If a signal handler
+/// raises (`KeyboardInterrupt` on Ctrl-C), every task in `states` is cancelled and the exception
+/// is returned instead.
+async fn interruptible<T, F>(states: &[Arc<TaskState>], future: F) -> PyResult<T>
+where
+    F: Future<Output = PyResult<T>>,
+{
+    let mut ticker: tokio::time::Interval = tokio::time::interval(SIGNAL_CHECK_INTERVAL);
+
+    tokio::pin!(future);
+
+    // The first tick completes immediately.
+    ticker.tick().await;
+
+    loop {
+        tokio::select! {
+            output = &mut future => return output,
+            _ = ticker.tick() => {
+                if let Err(error) = Python::with_gil(|py| py.check_signals()) {
+                    for state in states {
+                        state.cancel();
+                    }
+
+                    return Err(error);
+                }
+            }
+        }
+    }
+}
+
+/// Waits for every handle and returns their results in the order given, raising the first
+/// exception as soon as any task fails. `on_progress(completed, total)` is called after each
+/// completion, in completion order. A signal such as Ctrl-C interrupts the wait, cancelling every
+/// task that hasn't finished.
+#[pyfunction]
+#[pyo3(signature = (handles, on_progress = None))]
+pub fn gather(py: Python<'_>, handles: Vec<PyTaskHandle>, on_progress: Option<Py<PyAny>>) -> PyResult<Py<PyList>> {
+    let states: Vec<Arc<TaskState>> = handles.iter().map(|handle| handle.state.clone()).collect();
+    let total: usize = states.len();
+
+    py.allow_threads(|| {
+        runtime::block_on(interruptible(&states, async {
+            let mut set: JoinSet<usize> = JoinSet::new();
+
+            for (index, state) in states.iter().cloned().enumerate() {
+                set.spawn(async move {
+                    state.wait().await;
+                    index
+                });
+            }
+
+            let mut completed: usize = 0;
+
+            while let Some(joined) = set.join_next().await {
+                let index: usize = joined.map_err(|e: JoinError| {
+                    PyErr::new::<PyRuntimeError, _>(format!("Failed to gather tasks (task join error): {}", e))
+                })?;
+
+                completed += 1;
+
+                if let Some(callback) = &on_progress {
+                    Python::with_gil(|py_progress| callback.call1(py_progress, (completed, total)))?;
+                }
+
+                if states[index].failed() {
+                    return Python::with_gil(|py_error| states[index].output(py_error).map(drop));
+                }
+            }
+
+            Ok(())
+        }))
+    })??;
+
+    let results: Vec<PyObject> = states
+        .iter()
+        .map(|state| state.output(py))
+        .collect::<PyResult<Vec<PyObject>>>()?;
+
+    Ok(PyList::new(py, results)?.unbind())
+}
+
+/// Waits for every handle and returns one `(succeeded, value)` tuple per handle, in the order
+/// given: `(True, result)` for tasks that returned and `(False, exception)` for tasks that raised.
+/// Never raises because of a task's outcome, though a signal interrupts it like `gather`.
+#[pyfunction]
+pub fn try_gather(py: Python<'_>, handles: Vec<PyTaskHandle>) -> PyResult<Py<PyList>> {
+    let states: Vec<Arc<TaskState>> = handles.iter().map(|handle| handle.state.clone()).collect();
+
+    py.allow_threads(|| {
+        runtime::block_on(interruptible(&states, async {
+            for state in &states {
+                state.wait().await;
+            }
+
+            Ok(())
+        }))
+    })??;
+
+    let results: Vec<(bool, PyObject)> = states
+        .iter()
+        .map(|state| match state.output(py) {
+            Ok(value) => (true, value),
+            Err(error) => (false, error.into_value(py).into_any()),
+        })
+        .collect();
+
+    Ok(PyList::new(py, results)?.unbind())
+}
+
+fn task_entry<'py>(py: Python<'py>, state: Option<&TaskState>, tokio_id: Option<tokio::task::Id>) -> PyResult<Bound<'py, PyDict>> {
+    let entry: Bound<'py, PyDict> = PyDict::new(py);
+
+    entry.set_item("id", state.map(|state| state.id))?;
+    entry.set_item("name", state.map(|state| state.name.as_str()))?;
+    entry.set_item(
+        "tokio_id",
+        tokio_id.or_else(|| state.and_then(|state| state.abort.get()).map(AbortHandle::id)).map(|id| id.to_string()),
+    )?;
+
+    Ok(entry)
+}
+
+/// Lists the tasks coil has spawned that are still running, as dicts with their coil `id`,
+/// `name` and `tokio_id`.
+#[cfg(not(all(tokio_unstable, feature = "taskdump")))]
+#[pyfunction]
+pub fn task_dump(py: Python<'_>) -> PyResult<Py<PyList>> {
+    let entries: Vec<Bound<'_, PyDict>> = live_tasks()
+        .iter()
+        .map(|state| task_entry(py, Some(state), None))
+        .collect::<PyResult<_>>()?;
+
+    Ok(PyList::new(py, entries)?.unbind())
+}
+
+/// Lists every task on the runtime with a `trace` of where it is suspended, using tokio's task
+/// dumps. Tasks spawned by coil also carry their coil `id` and `name`; the rest (coil's own
+/// internal tasks) have those set to `None`.
+///
+/// Only available when built with `--cfg tokio_unstable` and the `taskdump` feature.
+#[cfg(all(tokio_unstable, feature = "taskdump"))]
+#[pyfunction]
+pub fn task_dump(py: Python<'_>) -> PyResult<Py<PyList>> {
+    let runtime: tokio::runtime::Handle = runtime::handle()?;
+    let dump: tokio::runtime::Dump = py.allow_threads(|| runtime::block_on(runtime.dump()))?;
+
+    let known: HashMap<tokio::task::Id, Arc<TaskState>> = live_tasks()
+        .into_iter()
+        .filter_map(|state| state.abort.get().map(|abort| (abort.id(), state.clone())))
+        .collect();
+
+    let entries: Vec<Bound<'_, PyDict>> = dump
+        .tasks()
+        .iter()
+        .map(|task| {
+            let entry: Bound<'_, PyDict> = task_entry(py, known.get(&task.id()).map(Arc::as_ref), Some(task.id()))?;
+            entry.set_item("trace", task.trace().to_string())?;
+
+            Ok(entry)
+        })
+        .collect::<PyResult<_>>()?;
+
+    Ok(PyList::new(py, entries)?.unbind())
+}
+
+/// The id of the coil task calling this, or `None` outside of one.
+#[pyfunction(name = "current_task_id")]
+pub fn py_current_task_id() -> Option<u64> {
+    current_task_id()
+}
@@ -0,0 +1,126 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use pyo3::prelude::*;
+use pyo3::types::PyFunction;
+use tokio::task::AbortHandle;
+
+use crate::errors;
+use crate::internal;
+use crate::runtime;
+
+struct TimerInner {
+    py_func: Arc<Py<PyFunction>>,
+    timeout_ns: u64,
+    // On `runtime::now_ns`'s clock. Resetting only moves this; the watcher notices when it wakes.
+    deadline_ns: AtomicU64,
+    // The task waiting for the deadline, if the timer is armed.
+    watcher: Mutex<Option<AbortHandle>>,
+    fired: AtomicU64,
+}
+
+impl TimerInner {
+    fn watcher(&self) -> MutexGuard<'_, Option<AbortHandle>> {
+        self.watcher.lock().expect("Timer watcher mutex was poisoned.")
+    }
+
+    // Runs on the blocking pool like any other callable, so it can take as long as it likes.
+    async fn fire(&self) {
+        self.fired.fetch_add(1, Ordering::SeqCst);
+
+        if let Err(error) = internal::exe_python_callable0_async(self.py_func.clone()).await {
+            errors::report_error_async(error, self.py_func.clone()).await;
+        }
+    }
+}
+
+async fn watch(timer: Arc<TimerInner>) {
+    loop {
+        let deadline: u64 = timer.deadline_ns.load(Ordering::SeqCst);
+        let now: u64 = runtime::now_ns();
+
+        if now < deadline {
+            tokio::time::sleep(Duration::from_nanos(deadline - now)).await;
+            continue
+        }
+
+        timer.fire().await;
+
+        // Checked under the lock `reset` takes, so a reset that came in while the callable ran
+        // either shows up here (and the timer keeps going) or sees the watcher gone and starts one.
+        let mut watcher: MutexGuard<'_, Option<AbortHandle>> = timer.watcher();
+
+        if timer.deadline_ns.load(Ordering::SeqCst) == deadline {
+            *watcher = None;
+            break
+        }
+    }
+}
+
+/// Calls `py_func()` once `timeout_ns` has passed without a call to `reset`, like a watchdog.
+#[pyclass(name = "ResettableTimer")]
+#[derive(Clone)]
+pub struct PyResettableTimer {
+    inner: Arc<TimerInner>,
+}
+
+impl PyResettableTimer {
+    fn arm(&self) -> PyResult<()> {
+        self.inner.deadline_ns.store(runtime::now_ns().saturating_add(self.inner.timeout_ns), Ordering::SeqCst);
+
+        let mut watcher: MutexGuard<'_, Option<AbortHandle>> = self.inner.watcher();
+
+        if watcher.is_none() {
+            *watcher = Some(runtime::handle()?.spawn(watch(self.inner.clone())).abort_handle());
+        }
+
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl PyResettableTimer {
+    /// Starts counting down immediately. An exception raised by `py_func` is reported.
+    #[new]
+    fn new(py_func: Py<PyFunction>, timeout_ns: u64) -> PyResult<Self> {
+        let timer: Self = Self {
+            inner: Arc::new(TimerInner {
+                py_func: Arc::new(py_func),
+                timeout_ns,
+                deadline_ns: AtomicU64::new(0),
+                watcher: Mutex::new(None),
+                fired: AtomicU64::new(0),
+            }),
+        };
+
+        timer.arm()?;
+
+        Ok(timer)
+    }
+
+    /// Restarts the countdown from now, re-arming the timer if it has already fired or been
+    /// cancelled. Cheap enough to call on every heartbeat: it is one atomic store and an
+    /// uncontended lock.
+    pub fn reset(&self) -> PyResult<()> {
+        self.arm()
+    }
+
+    /// Stops the countdown without firing. A callable that is already running is not interrupted.
+    pub fn cancel(&self) {
+        if let Some(watcher) = self.inner.watcher().take() {
+            watcher.abort();
+        }
+    }
+
+    /// Whether the timer is counting down.
+    pub fn is_armed(&self) -> bool {
+        self.inner.watcher().is_some()
+    }
+
+    /// How many times the timer has fired.
+    #[getter]
+    pub fn fired(&self) -> u64 {
+        self.inner.fired.load(Ordering::SeqCst)
+    }
+}
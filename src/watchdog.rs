@@ -0,0 +1,99 @@
+use std::os::raw::c_long;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Duration;
+
+use pyo3::exceptions::{PyKeyboardInterrupt, PyRuntimeError};
+use pyo3::prelude::*;
+use pyo3::PyTypeInfo;
+use pyo3::types::{PyFunction, PyModule, PyTuple};
+
+use crate::errors::TaskTimeout;
+use crate::gil;
+use crate::hooks;
+
+/// Calls `py_func(*args)` on a new OS thread and waits up to `timeout_ns` for it, with the GIL
+/// released. If it returns in time, so does this, with its result or exception. If not, this
+/// raises `TaskTimeout` and tries to stop the call by raising `KeyboardInterrupt` inside it.
+///
+/// That second part is a last resort with real sharp edges:
+///
+/// - The exception is only delivered when the thread next runs Python bytecode. A call stuck in
+///   C code (a blocking socket read, `time.sleep`, a lock) isn't interrupted until it comes back
+///   to Python, if it ever does, and the thread lingers until then.
+/// - It can land anywhere in the callable, including inside a `finally` or `__exit__` that was
+///   cleaning up, so locks may be left held and state half-updated. `except BaseException` or a
+///   bare `except` in the callable swallows it.
+/// - Nothing is returned from the interrupted call; its outcome is discarded.
+///
+/// Prefer a callable that checks a flag or a deadline itself, and only use this for code that
+/// can't be changed to.
+#[pyfunction]
+#[pyo3(signature = (py_func, args, timeout_ns))]
+pub fn call_with_hard_timeout(py: Python<'_>, py_func: Py<PyFunction>, args: Py<PyTuple>, timeout_ns: u64) -> PyResult<PyObject> {
+    let (sender, receiver) = mpsc::channel::<PyResult<PyObject>>();
+    let (ident_sender, ident_receiver) = mpsc::channel::<c_long>();
+    // Both only ever read or written with the GIL held. A worker that hasn't started the call
+    // when it times out sees `abandoned` and never does; one that has is still inside it as long
+    // as `finished` is unset, so the exception can't land after the call.
+    let abandoned: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    let finished: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    let (worker_abandoned, worker_finished): (Arc<AtomicBool>, Arc<AtomicBool>) = (abandoned.clone(), finished.clone());
+
+    std::thread::Builder::new()
+        .name("coil-hard-timeout".to_string())
+        .spawn(move || {
+            let _ = gil::attach(move |py_worker| {
+                hooks::prepare_thread(py_worker);
+
+                let ident: PyResult<u64> = PyModule::import(py_worker, "threading")
+                    .and_then(|threading| threading.getattr("get_ident")?.call0()?.extract::<u64>());
+
+                match ident {
+                    Ok(ident) => {
+                        let _ = ident_sender.send(ident as c_long);
+                    }
+                    Err(error) => {
+                        let _ = sender.send(Err(error));
+                        return
+                    }
+                }
+
+                if worker_abandoned.load(Ordering::SeqCst) {
+                    return
+                }
+
+                let result: PyResult<PyObject> = py_func.call1(py_worker, args.bind(py_worker));
+                worker_finished.store(true, Ordering::SeqCst);
+
+                let _ = sender.send(result);
+            });
+        })
+        .map_err(|e: std::io::Error| PyErr::new::<PyRuntimeError, _>(format!("Failed to start a thread for the call: {}", e)))?;
+
+    let timeout: Duration = Duration::from_nanos(timeout_ns);
+    let outcome: Result<PyResult<PyObject>, RecvTimeoutError> = py.allow_threads(move || receiver.recv_timeout(timeout));
+
+    match outcome {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Disconnected) => {
+            Err(PyErr::new::<PyRuntimeError, _>("The thread running the call exited without a result."))
+        }
+        Err(RecvTimeoutError::Timeout) => {
+            abandoned.store(true, Ordering::SeqCst);
+
+            if let Ok(ident) = ident_receiver.try_recv()
+                && !finished.load(Ordering::SeqCst)
+            {
+                // Safety: called with the GIL held; an ident that no longer matches a thread is
+                // simply ignored by CPython.
+                unsafe {
+                    pyo3::ffi::PyThreadState_SetAsyncExc(ident, PyKeyboardInterrupt::type_object(py).as_ptr());
+                }
+            }
+
+            Err(PyErr::new::<TaskTimeout, _>(format!("The call did not finish within {timeout:?}.")))
+        }
+    }
+}
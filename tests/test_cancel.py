@@ -0,0 +1,53 @@
+import unittest
+
+import coil_core as cc
+
+
+class CancelTest(unittest.TestCase):
+    def test_cancel_reaches_grandchildren(self):
+        gate = cc.Event()
+
+        def wait(_):
+            gate.wait(10.0)
+
+        parent = cc.spawn_or_raise(wait, None)
+        child = cc.spawn_or_raise(wait, None, parent=parent)
+        grandchild = cc.spawn_or_raise(wait, None, parent=child)
+
+        try:
+            self.assertTrue(parent.cancel())
+
+            self.assertTrue(child.is_cancelled())
+            self.assertTrue(grandchild.is_cancelled())
+
+            for handle in (parent, child, grandchild):
+                with self.assertRaises(cc.TaskCancelled):
+                    handle.join()
+        finally:
+            gate.set()
+
+        cc.wait_until_idle()
+
+    def test_cancelling_a_child_leaves_the_parent_alone(self):
+        gate = cc.Event()
+
+        def wait(_):
+            gate.wait(10.0)
+            return "done"
+
+        parent = cc.spawn_or_raise(wait, None)
+        child = cc.spawn_or_raise(wait, None, parent=parent)
+        grandchild = cc.spawn_or_raise(wait, None, parent=child)
+
+        self.assertTrue(child.cancel())
+        gate.set()
+
+        self.assertTrue(grandchild.is_cancelled())
+        self.assertFalse(parent.is_cancelled())
+        self.assertEqual(parent.join(), "done")
+
+        cc.wait_until_idle()
+
+
+if __name__ == "__main__":
+    unittest.main()
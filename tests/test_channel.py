@@ -0,0 +1,80 @@
+import asyncio
+import threading
+import time
+import unittest
+
+import coil_core as cc
+
+
+def produce(channel, count):
+    for item in range(count):
+        channel.send(item)
+
+        if item % 5 == 0:
+            time.sleep(0.0001)
+
+    channel.close()
+
+
+def drain(channel):
+    items = []
+
+    try:
+        while (item := channel.try_recv()) is not None:
+            items.append(item)
+    except cc.QueueClosed:
+        pass
+
+    return items
+
+
+class RecvAsyncTest(unittest.TestCase):
+    def test_cancelled_receives_lose_nothing(self):
+        channel = cc.Channel()
+
+        async def receive_all():
+            items = []
+            threading.Thread(target=produce, args=(channel, 500)).start()
+
+            while True:
+                future = channel.recv_async()
+                await asyncio.wait({future}, timeout=0.0003)
+
+                if not future.done():
+                    future.cancel()
+                    continue
+
+                try:
+                    items.append(future.result())
+                except cc.QueueClosed:
+                    break
+
+            # A cancelled receive can hand its item back after the close has been seen.
+            await asyncio.sleep(0.1)
+
+            return items + drain(channel)
+
+        self.assertEqual(sorted(asyncio.run(receive_all())), list(range(500)))
+
+    def test_timeouts_lose_nothing(self):
+        channel = cc.Channel()
+
+        async def receive_all():
+            items = []
+            threading.Thread(target=produce, args=(channel, 500)).start()
+
+            while True:
+                try:
+                    items.append(await channel.recv_async(0.0005))
+                except TimeoutError:
+                    continue
+                except cc.QueueClosed:
+                    break
+
+            return items
+
+        self.assertEqual(asyncio.run(receive_all()), list(range(500)))
+
+
+if __name__ == "__main__":
+    unittest.main()
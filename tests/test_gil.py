@@ -0,0 +1,63 @@
+import subprocess
+import sys
+import textwrap
+import unittest
+
+
+def run_script(script):
+    return subprocess.run([sys.executable, "-c", textwrap.dedent(script)], capture_output=True, text=True, timeout=60)
+
+
+class GilTest(unittest.TestCase):
+    def test_spawn_from_a_fresh_thread_and_context(self):
+        # The interpreter's first use of coil happens on a thread that has never run Python
+        # before, inside an empty `contextvars` context, so nothing is set up ahead of time.
+        result = run_script("""
+            import contextvars, threading
+            import coil_core as cc
+
+            def double(x):
+                return x * 2
+
+            results = []
+
+            def spawn():
+                results.append(cc.new_thread(double, 21).join())
+
+            thread = threading.Thread(target=lambda: contextvars.Context().run(spawn))
+            thread.start()
+            thread.join()
+
+            print(results)
+        """)
+
+        self.assertEqual(result.returncode, 0, result.stderr)
+        self.assertEqual(result.stdout.strip(), "[42]")
+
+    def test_spawn_while_finalizing_raises(self):
+        # Registered before coil is imported, so it runs after coil's own exit hooks.
+        result = run_script("""
+            import atexit
+
+            def noop(_):
+                return None
+
+            def spawn_late():
+                try:
+                    cc.new_thread(noop, None)
+                except RuntimeError as error:
+                    print(f"RuntimeError: {error}")
+                else:
+                    print("spawned")
+
+            atexit.register(spawn_late)
+
+            import coil_core as cc
+        """)
+
+        self.assertEqual(result.returncode, 0, result.stderr)
+        self.assertEqual(result.stdout.strip(), "RuntimeError: The Python interpreter is shutting down.")
+
+
+if __name__ == "__main__":
+    unittest.main()
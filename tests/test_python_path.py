@@ -0,0 +1,50 @@
+import subprocess
+import sys
+import textwrap
+import unittest
+
+import coil_core as cc
+
+
+def noop(_):
+    return None
+
+
+class PythonPathTest(unittest.TestCase):
+    def test_spawning_and_reimporting_add_at_most_one_entry(self):
+        # In a fresh interpreter, since this one has already had its path set up.
+        script = textwrap.dedent("""
+            import importlib, sys
+
+            def noop(_):
+                return None
+
+            while "." in sys.path:
+                sys.path.remove(".")
+
+            for _ in range(3):
+                sys.modules.pop("coil_core", None)
+                cc = importlib.import_module("coil_core")
+                for handle in [cc.new_thread(noop, None) for _ in range(200)]:
+                    handle.join()
+
+            print(sys.path.count("."))
+        """)
+
+        result = subprocess.run([sys.executable, "-c", script], capture_output=True, text=True, timeout=60)
+
+        self.assertEqual(result.returncode, 0, result.stderr)
+        self.assertEqual(result.stdout.strip(), "1")
+
+    def test_spawning_does_not_touch_an_existing_entry(self):
+        cc.new_thread(noop, None).join()
+        before = list(sys.path)
+
+        for handle in [cc.new_thread(noop, None) for _ in range(200)]:
+            handle.join()
+
+        self.assertEqual(sys.path, before)
+
+
+if __name__ == "__main__":
+    unittest.main()
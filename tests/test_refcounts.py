@@ -0,0 +1,39 @@
+import sys
+import unittest
+
+import coil_core as cc
+
+
+def work(arg):
+    return arg
+
+
+class RefcountTest(unittest.TestCase):
+    def test_spawned_callable_and_arg_are_released(self):
+        arg = object()
+        baseline = (sys.getrefcount(work), sys.getrefcount(arg))
+
+        handles = [cc.new_thread(work, arg) for _ in range(2000)]
+        for handle in handles:
+            self.assertIs(handle.join(), arg)
+        del handle, handles
+        cc.wait_until_idle()
+
+        self.assertEqual((sys.getrefcount(work), sys.getrefcount(arg)), baseline)
+
+    def test_queued_items_are_released_when_the_channel_is_dropped(self):
+        item = object()
+        baseline = sys.getrefcount(item)
+
+        channel = cc.Channel()
+        for _ in range(5000):
+            channel.send(item)
+        for _ in range(2500):
+            channel.recv()
+        del channel
+
+        self.assertEqual(sys.getrefcount(item), baseline)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,35 @@
+import threading
+import time
+import unittest
+
+import coil_core as cc
+
+
+class SleepTest(unittest.TestCase):
+    def test_sleep_releases_the_gil(self):
+        started = threading.Event()
+        woke_at = []
+
+        def sleeper():
+            started.set()
+            cc.sleep(1.0)
+            woke_at.append(time.monotonic())
+
+        thread = threading.Thread(target=sleeper)
+        thread.start()
+        started.wait()
+
+        # Gives the sleeper time to get into `cc.sleep`. If that held on to the GIL, this thread
+        # couldn't run again until it returned.
+        time.sleep(0.2)
+        total = sum(range(10_000))
+        progressed_at = time.monotonic()
+
+        thread.join()
+
+        self.assertEqual(total, 49_995_000)
+        self.assertLess(progressed_at, woke_at[0])
+
+
+if __name__ == "__main__":
+    unittest.main()